@@ -8,7 +8,7 @@ fuzz_target!(|data_str: &str| {
     }
 
     //println!("{}", data_str);
-    for _l in jsdu::size::JsonSize::new(data_str).display_list(data_str) {
+    for _l in jsdu::size::JsonSize::new(data_str).display_list(data_str, &Default::default()) {
         //println!("{}", l);
     }
 
@@ -0,0 +1,217 @@
+//! Read JSON files inside zip and tar archives without extracting them to disk. A file inside an
+//! archive is selected with a path like `archive.zip!/data/users.json`.
+use std::fmt;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+}
+
+impl ArchiveFormat {
+    /// Format of an archive that starts with `magic`, after decompressing it. Tar archives are
+    /// recognized by the "ustar" magic at offset 257, so at least 262 bytes are needed.
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(b"PK\x03\x04") || magic.starts_with(b"PK\x05\x06") {
+            Some(ArchiveFormat::Zip)
+        } else if magic.get(257..262) == Some(b"ustar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Tar => "tar",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A regular file inside an archive
+#[derive(Debug, PartialEq, Eq)]
+pub struct Member {
+    /// Path inside the archive, without leading `/`
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+fn invalid_data(e: impl fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+/// Call `f` with every regular file of the archive, in archive order. Stops early if `f` returns
+/// false.
+fn for_each_member(
+    archive: &[u8],
+    mut f: impl FnMut(&str, &mut dyn Read) -> io::Result<bool>,
+) -> io::Result<()> {
+    match ArchiveFormat::detect(archive) {
+        Some(ArchiveFormat::Zip) => {
+            let mut zip = zip::ZipArchive::new(Cursor::new(archive)).map_err(invalid_data)?;
+            for i in 0..zip.len() {
+                let mut file = zip.by_index(i).map_err(invalid_data)?;
+                if !file.is_file() {
+                    continue;
+                }
+                let name = file.name().trim_start_matches('/').to_string();
+                if !f(&name, &mut file)? {
+                    break;
+                }
+            }
+        }
+        Some(ArchiveFormat::Tar) => {
+            let mut tar = tar::Archive::new(archive);
+            for entry in tar.entries()? {
+                let mut entry = entry?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let path = entry.path()?.to_string_lossy().into_owned();
+                let name = path
+                    .trim_start_matches("./")
+                    .trim_start_matches('/')
+                    .to_string();
+                if !f(&name, &mut entry)? {
+                    break;
+                }
+            }
+        }
+        None => return Err(invalid_data("not a zip or tar archive")),
+    }
+
+    Ok(())
+}
+
+/// All the regular files of an archive, in archive order
+pub fn members(archive: &[u8]) -> io::Result<Vec<Member>> {
+    let mut members = vec![];
+    for_each_member(archive, |name, reader| {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        members.push(Member {
+            name: name.to_string(),
+            data,
+        });
+        Ok(true)
+    })?;
+
+    Ok(members)
+}
+
+/// Contents of the file called `name` inside the archive
+pub fn read_member(archive: &[u8], name: &str) -> io::Result<Vec<u8>> {
+    let name = name.trim_start_matches('/');
+    let mut found = None;
+    for_each_member(archive, |member_name, reader| {
+        if member_name != name {
+            return Ok(true);
+        }
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        found = Some(data);
+        Ok(false)
+    })?;
+
+    found.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no file named {:?} in the archive", name),
+        )
+    })
+}
+
+/// Split `archive.zip!/data/users.json` into the path of the archive and the name of the file
+/// inside it. Returns `None` if the path does not contain `!/`, or if the part before it is not
+/// an existing file.
+pub fn split_member_path(path: &Path) -> Option<(PathBuf, String)> {
+    let path = path.to_str()?;
+    let mut start = 0;
+    while let Some(i) = path[start..].find("!/") {
+        let (archive, member) = path.split_at(start + i);
+        let archive = Path::new(archive);
+        if archive.is_file() {
+            return Some((archive.to_path_buf(), member[2..].to_string()));
+        }
+        start += i + 2;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn zip_archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        for (name, contents) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.add_directory("empty/", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn tar_archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut tar = tar::Builder::new(vec![]);
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, contents.as_bytes())
+                .unwrap();
+        }
+        tar.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_members() {
+        let files = [("a.json", "[1, 2]"), ("data/users.json", r#"{"x": 1}"#)];
+        for (archive, format) in [
+            (zip_archive(&files), ArchiveFormat::Zip),
+            (tar_archive(&files), ArchiveFormat::Tar),
+        ] {
+            assert_eq!(ArchiveFormat::detect(&archive), Some(format));
+            let members = members(&archive).unwrap();
+            let names: Vec<_> = members.iter().map(|x| x.name.as_str()).collect();
+            assert_eq!(names, vec!["a.json", "data/users.json"]);
+            assert_eq!(
+                read_member(&archive, "/data/users.json").unwrap(),
+                br#"{"x": 1}"#
+            );
+            assert_eq!(
+                read_member(&archive, "b.json").unwrap_err().kind(),
+                io::ErrorKind::NotFound
+            );
+        }
+        assert_eq!(ArchiveFormat::detect(b"[1, 2]"), None);
+    }
+
+    #[test]
+    fn test_split_member_path() {
+        let dir = std::env::temp_dir().join(format!("jsdu-archive-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("a!/b.zip");
+        std::fs::create_dir_all(archive.parent().unwrap()).unwrap();
+        std::fs::write(&archive, zip_archive(&[])).unwrap();
+
+        let path = PathBuf::from(format!("{}!/data/users.json", archive.display()));
+        assert_eq!(
+            split_member_path(&path),
+            Some((archive.clone(), "data/users.json".to_string()))
+        );
+        assert_eq!(split_member_path(&archive), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
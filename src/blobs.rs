@@ -0,0 +1,257 @@
+//! Find base64 strings and data URIs, decode them so they can be saved to files, and replace
+//! them with references to those files.
+use crate::payload::{decode_base64, Payload, PayloadClass};
+use crate::size::{unescape_json_string, JsonSize, JsonValueKind};
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// A decoded string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blob {
+    /// Path of child indices of the string
+    pub path: Vec<usize>,
+    /// Span of the string in the JSON file, including quotes
+    pub span: Range<usize>,
+    pub data: Vec<u8>,
+    /// MIME type, only for data URIs
+    pub mime: Option<String>,
+}
+
+impl Blob {
+    /// File extension guessed from the first bytes of the data, or from the MIME type
+    pub fn extension(&self) -> &'static str {
+        let data = &self.data;
+        let magic: [(&[u8], &str); 10] = [
+            (b"\x89PNG\r\n\x1a\n", "png"),
+            (b"\xff\xd8\xff", "jpg"),
+            (b"GIF87a", "gif"),
+            (b"GIF89a", "gif"),
+            (b"%PDF-", "pdf"),
+            (b"PK\x03\x04", "zip"),
+            (b"\x1f\x8b", "gz"),
+            (b"\x28\xb5\x2f\xfd", "zst"),
+            (b"BM", "bmp"),
+            (b"\x00\x00\x01\x00", "ico"),
+        ];
+        if let Some((_, extension)) = magic.iter().find(|(magic, _)| data.starts_with(magic)) {
+            return extension;
+        }
+        if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+            return "webp";
+        }
+        if data.len() >= 12 && &data[4..8] == b"ftyp" {
+            return "mp4";
+        }
+        if let Ok(text) = std::str::from_utf8(data) {
+            let text = text.trim_start();
+            if text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg")) {
+                return "svg";
+            }
+            if text.starts_with("<?xml") {
+                return "xml";
+            }
+            if JsonSize::try_new(text).is_some() {
+                return "json";
+            }
+        }
+
+        match self.mime.as_deref() {
+            Some("image/png") => "png",
+            Some("image/jpeg") => "jpg",
+            Some("image/gif") => "gif",
+            Some("image/webp") => "webp",
+            Some("image/svg+xml") => "svg",
+            Some("application/pdf") => "pdf",
+            Some("application/json") => "json",
+            Some(mime) if mime.starts_with("text/") => "txt",
+            _ => "bin",
+        }
+    }
+}
+
+/// All the base64 strings and data URIs below `js` whose decoded size is at least `min_size`
+/// bytes, in document order. `path` is the location of `js`.
+pub fn find_blobs(js: &JsonSize, json: &str, path: Vec<usize>, min_size: usize) -> Vec<Blob> {
+    let mut blobs = vec![];
+    let mut stack = vec![(path, js)];
+    while let Some((path, node)) = stack.pop() {
+        for (i, child) in node.children().iter().enumerate().rev() {
+            let mut child_path = path.clone();
+            child_path.push(i);
+            stack.push((child_path, child));
+        }
+        if node.value_kind() != JsonValueKind::String {
+            continue;
+        }
+        let span = node.span();
+        let raw = &json[span.start + 1..span.end - 1];
+        let payload = Payload::classify(raw);
+        if payload.decoded_size.unwrap_or(0) < min_size {
+            continue;
+        }
+        let data = match payload.class {
+            PayloadClass::Base64 => decode_base64(&unescape_json_string(raw)),
+            PayloadClass::DataUri => decode_data_uri(raw),
+            _ => None,
+        };
+        if let Some(data) = data {
+            blobs.push(Blob {
+                path,
+                span,
+                data,
+                mime: payload.mime,
+            });
+        }
+    }
+
+    blobs
+}
+
+/// Decode the data of a data URI, either base64 or percent-encoded
+fn decode_data_uri(raw: &str) -> Option<Vec<u8>> {
+    let (header, data) = raw.strip_prefix("data:")?.split_once(',')?;
+    let data = unescape_json_string(data);
+    if header.ends_with(";base64") {
+        // MIME wraps base64 in lines
+        let data: String = data.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+        return decode_base64(&data);
+    }
+
+    let bytes = data.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    Some(decoded)
+}
+
+/// Longest stem of `file_name`, most file systems allow 255 bytes including the suffix
+const MAX_STEM_LEN: usize = 200;
+
+/// Turn a JSON pointer into a file name: "/users/0/avatar" becomes "users_0_avatar". Characters
+/// that are not safe in file names are replaced by `_`, long names are truncated, and a number
+/// is appended if the name is already in `used`.
+pub fn file_name(pointer: &str, extension: &str, used: &mut HashSet<String>) -> String {
+    let mut stem: String = pointer
+        .trim_start_matches('/')
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    // Only ASCII is left
+    stem.truncate(MAX_STEM_LEN);
+    let stem = if stem.is_empty() { "root" } else { &stem };
+    let mut name = format!("{}.{}", stem, extension);
+    let mut n = 2;
+    while used.contains(&name) {
+        name = format!("{}-{}.{}", stem, n, extension);
+        n += 1;
+    }
+    used.insert(name.clone());
+
+    name
+}
+
+/// Replace each blob with a string containing its reference. `blobs` must be in document order.
+pub fn replace_blobs(json: &str, blobs: &[Blob], references: &[String]) -> String {
+    let mut out = String::with_capacity(json.len());
+    let mut last = 0;
+    for (blob, reference) in blobs.iter().zip(references) {
+        out.push_str(&json[last..blob.span.start]);
+        out.push('"');
+        for c in reference.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+        last = blob.span.end;
+    }
+    out.push_str(&json[last..]);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_blobs() {
+        // PNG signature in base64, and a percent-encoded SVG
+        let json =
+            r#"{"a": ["iVBORw0KGgoAAAAA", "short"], "b": "data:image/svg+xml,%3Csvg%2F%3E"}"#;
+        let js = JsonSize::new(json);
+        let blobs = find_blobs(&js, json, vec![], 4);
+
+        assert_eq!(blobs.len(), 2);
+        assert_eq!(blobs[0].path, vec![0, 0]);
+        assert_eq!(&json[blobs[0].span.clone()], r#""iVBORw0KGgoAAAAA""#);
+        assert_eq!(blobs[0].extension(), "png");
+        assert_eq!(blobs[1].data, b"<svg/>");
+        assert_eq!(blobs[1].extension(), "svg");
+
+        assert_eq!(find_blobs(&js, json, vec![], 100), vec![]);
+    }
+
+    #[test]
+    fn test_file_name() {
+        let mut used = HashSet::new();
+        assert_eq!(
+            file_name("/users/0/avatar", "png", &mut used),
+            "users_0_avatar.png"
+        );
+        assert_eq!(
+            file_name("/users_0/avatar", "png", &mut used),
+            "users_0_avatar-2.png"
+        );
+        assert_eq!(file_name("/a~1b/ü", "bin", &mut used), "a_1b__.bin");
+        assert_eq!(file_name("", "bin", &mut used), "root.bin");
+
+        let long = format!("/{}", "k".repeat(300));
+        let name = file_name(&long, "bin", &mut used);
+        assert_eq!(name, format!("{}.bin", "k".repeat(MAX_STEM_LEN)));
+        assert_eq!(
+            file_name(&long, "bin", &mut used),
+            format!("{}-2.bin", "k".repeat(MAX_STEM_LEN))
+        );
+    }
+
+    #[test]
+    fn test_wrapped_data_uri() {
+        // PNG signature split in two lines, and an escaped "+"
+        let json = r#"["data:image/png;base64,iVBORw0K
+GgoAAAAA", "data:;base64,\u002B/8="]"#;
+        let js = JsonSize::new(json);
+        let blobs = find_blobs(&js, json, vec![], 2);
+        assert_eq!(blobs.len(), 2);
+        assert_eq!(blobs[0].extension(), "png");
+        assert_eq!(blobs[1].data, vec![0xfb, 0xff]);
+    }
+
+    #[test]
+    fn test_replace_blobs() {
+        let json = r#"{"a": "iVBORw0KGgoAAAAA", "b": 1}"#;
+        let js = JsonSize::new(json);
+        let blobs = find_blobs(&js, json, vec![], 4);
+        let replaced = replace_blobs(json, &blobs, &["blobs/a.png".to_string()]);
+        assert_eq!(replaced, r#"{"a": "blobs/a.png", "b": 1}"#);
+    }
+}
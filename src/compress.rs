@@ -0,0 +1,156 @@
+//! Estimate the size of JSON data after compressing it with gzip or zstd.
+use std::io::Write;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Codec {
+    Gzip,
+    Zstd,
+}
+
+/// A compression algorithm and level
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Compressor {
+    pub codec: Codec,
+    pub level: i32,
+}
+
+impl std::str::FromStr for Compressor {
+    type Err = String;
+
+    /// Parse "gzip" or "zstd", optionally followed by a level: "gzip:9", "zstd:19"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, level) = match s.split_once(':') {
+            Some((name, level)) => (
+                name,
+                Some(
+                    level
+                        .parse()
+                        .map_err(|_| format!("invalid compression level {:?}", level))?,
+                ),
+            ),
+            None => (s, None),
+        };
+        let (codec, default_level, levels) = match name {
+            "gzip" => (Codec::Gzip, 6, 0..=9),
+            "zstd" => (Codec::Zstd, 3, 1..=22),
+            _ => return Err(format!("expected \"gzip\" or \"zstd\", got {:?}", name)),
+        };
+        let level = level.unwrap_or(default_level);
+        if !levels.contains(&level) {
+            return Err(format!(
+                "{} level must be between {} and {}, got {}",
+                name,
+                levels.start(),
+                levels.end(),
+                level
+            ));
+        }
+
+        Ok(Compressor { codec, level })
+    }
+}
+
+impl std::fmt::Display for Compressor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self.codec {
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+        };
+        write!(f, "{}:{}", name, self.level)
+    }
+}
+
+impl Compressor {
+    /// Size of `data` after compressing it, including the format headers
+    pub fn compressed_size(&self, data: &[u8]) -> usize {
+        match self.codec {
+            Codec::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(
+                    Vec::new(),
+                    flate2::Compression::new(self.level as u32),
+                );
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap().len()
+            }
+            Codec::Zstd => zstd::bulk::compress(data, self.level).unwrap().len(),
+        }
+    }
+
+    /// Header of the columns added by `to_display`
+    pub fn header(&self) -> String {
+        format!("{:>10} {:>6}", self.to_string(), "Ratio")
+    }
+
+    /// Compressed size of `data` and compression ratio, in columns
+    pub fn to_display(&self, data: &[u8]) -> String {
+        let compressed_size = self.compressed_size(data);
+        format!(
+            "{:10} {:5.1}x",
+            compressed_size,
+            data.len() as f64 / compressed_size as f64
+        )
+    }
+}
+
+/// Compressed size of the whole file, as it is, with each compressor
+pub fn display_file_sizes(json: &str, compressors: &[Compressor]) -> String {
+    let sizes: Vec<String> = compressors
+        .iter()
+        .map(|compressor| {
+            format!(
+                "{} {}",
+                compressor,
+                compressor.compressed_size(json.as_bytes())
+            )
+        })
+        .collect();
+    format!("File: {} bytes, {}", json.len(), sizes.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_compressor() {
+        assert_eq!(
+            "gzip".parse(),
+            Ok(Compressor {
+                codec: Codec::Gzip,
+                level: 6
+            })
+        );
+        assert_eq!(
+            "zstd:19".parse(),
+            Ok(Compressor {
+                codec: Codec::Zstd,
+                level: 19
+            })
+        );
+        assert!("gzip:10".parse::<Compressor>().is_err());
+        assert!("zstd:x".parse::<Compressor>().is_err());
+        assert!("brotli".parse::<Compressor>().is_err());
+    }
+
+    #[test]
+    fn test_redundant_data_compresses_well() {
+        let repeated = "[1,2,3,4,5,6,7,8]".repeat(100);
+        // xorshift, to get letters without any pattern
+        let mut state: u64 = 0x2545f4914f6cdd1d;
+        let random: String = (0..repeated.len())
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                char::from(b'a' + (state % 26) as u8)
+            })
+            .collect();
+        for compressor in ["gzip", "zstd"] {
+            let compressor: Compressor = compressor.parse().unwrap();
+            let repeated_size = compressor.compressed_size(repeated.as_bytes());
+            let random_size = compressor.compressed_size(random.as_bytes());
+            assert!(repeated_size * 10 < repeated.len());
+            assert!(random_size * 2 > random.len());
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Read files compressed with gzip, zstd, bzip2 or xz, detected by their magic bytes, without
+//! decompressing them to disk first.
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    /// Format of a file that starts with `magic`, `None` if it is not compressed
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        match magic {
+            [0x1F, 0x8B, ..] => Some(Compression::Gzip),
+            [0x28, 0xB5, 0x2F, 0xFD, ..] => Some(Compression::Zstd),
+            [b'B', b'Z', b'h', ..] => Some(Compression::Bzip2),
+            [0xFD, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+
+    /// Decompress `reader` on the fly. Files with many concatenated streams are read until the
+    /// end, like `zcat` does.
+    pub fn decoder<'a>(self, reader: impl BufRead + 'a) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+            Compression::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(reader)),
+            Compression::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+        })
+    }
+
+    /// Compress `data` with the default level of each format
+    pub fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::Zstd => zstd::stream::encode_all(data, 0),
+            Compression::Bzip2 => {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Bzip2 => "bzip2",
+            Compression::Xz => "xz",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Wrap `reader` in a decoder if it starts with the magic bytes of a compressed format
+pub fn open_reader<'a>(
+    mut reader: impl BufRead + 'a,
+) -> io::Result<(Box<dyn BufRead + 'a>, Option<Compression>)> {
+    match Compression::detect(reader.fill_buf()?) {
+        Some(compression) => {
+            let decoder = BufReader::new(compression.decoder(reader)?);
+            Ok((Box::new(decoder), Some(compression)))
+        }
+        None => Ok((Box::new(reader), None)),
+    }
+}
+
+/// Open a file, or stdin if the path is "-", decompressing it if needed
+pub fn open(path: &Path) -> io::Result<(Box<dyn BufRead>, Option<Compression>)> {
+    if path == Path::new("-") {
+        open_reader(io::stdin().lock())
+    } else {
+        open_reader(BufReader::new(fs::File::open(path)?))
+    }
+}
+
+/// Read a whole file, or stdin, decompressing it if needed
+pub fn read(path: &Path) -> io::Result<(Vec<u8>, Option<Compression>)> {
+    let (mut reader, compression) = open(path)?;
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+
+    Ok((bytes, compression))
+}
+
+/// Decompress `bytes` if they start with the magic bytes of a compressed format
+pub fn decompress(bytes: Vec<u8>) -> io::Result<(Vec<u8>, Option<Compression>)> {
+    match Compression::detect(&bytes) {
+        Some(compression) => {
+            let mut decompressed = vec![];
+            compression
+                .decoder(&bytes[..])?
+                .read_to_end(&mut decompressed)?;
+            Ok((decompressed, Some(compression)))
+        }
+        None => Ok((bytes, None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let json = br#"{"a": [1, 2, 3], "b": "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"}"#;
+        for compression in [
+            Compression::Gzip,
+            Compression::Zstd,
+            Compression::Bzip2,
+            Compression::Xz,
+        ] {
+            let compressed = compression.compress(json).unwrap();
+            assert_eq!(Compression::detect(&compressed), Some(compression));
+            let (mut reader, detected) = open_reader(&compressed[..]).unwrap();
+            assert_eq!(detected, Some(compression));
+            let mut decompressed = vec![];
+            reader.read_to_end(&mut decompressed).unwrap();
+            assert_eq!(decompressed, json);
+        }
+    }
+
+    #[test]
+    fn test_uncompressed_and_concatenated() {
+        let (mut reader, detected) = open_reader(&b"[1]"[..]).unwrap();
+        assert_eq!(detected, None);
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "[1]");
+
+        // Like `cat a.gz b.gz`
+        let mut compressed = Compression::Gzip.compress(b"{\"a\": 1}\n").unwrap();
+        compressed.extend(Compression::Gzip.compress(b"{\"b\": 2}\n").unwrap());
+        let (mut reader, _) = open_reader(&compressed[..]).unwrap();
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "{\"a\": 1}\n{\"b\": 2}\n");
+    }
+}
@@ -0,0 +1,199 @@
+//! Distribution of the sizes of the items of an array, to find abnormal items in otherwise
+//! uniform arrays.
+use crate::size::{JsonSize, JsonValueKind};
+
+/// Summary of the sizes of the items of one array
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArrayDistribution {
+    /// Path of child indices from the root to the array
+    pub path: Vec<usize>,
+    pub total_size: usize,
+    /// Item sizes, sorted
+    sizes: Vec<usize>,
+    /// Indices of the items whose size is more than `outlier_factor` times the median, biggest
+    /// first
+    pub outliers: Vec<(usize, usize)>,
+}
+
+impl ArrayDistribution {
+    pub fn new(array: &JsonSize, path: Vec<usize>, outlier_factor: f64) -> Self {
+        let mut sizes: Vec<usize> = array.children().iter().map(|x| x.total_size()).collect();
+        sizes.sort_unstable();
+        let median = percentile(&sizes, 50.0);
+        let mut outliers: Vec<(usize, usize)> = array
+            .children()
+            .iter()
+            .map(|x| (x.total_size(), x.key().index()))
+            .filter(|&(size, _)| size as f64 > median as f64 * outlier_factor)
+            .collect();
+        outliers.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+        Self {
+            path,
+            total_size: array.total_size(),
+            sizes,
+            outliers,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    pub fn min(&self) -> usize {
+        self.sizes.first().copied().unwrap_or(0)
+    }
+
+    pub fn max(&self) -> usize {
+        self.sizes.last().copied().unwrap_or(0)
+    }
+
+    /// Item size at percentile `p` (0 to 100), nearest rank method
+    pub fn percentile(&self, p: f64) -> usize {
+        percentile(&self.sizes, p)
+    }
+
+    /// Number of items with size in each power of two range: `(lower bound, count)`
+    pub fn histogram(&self) -> Vec<(usize, usize)> {
+        let bucket = |size: usize| match size {
+            0 => 0,
+            x => x.ilog2() as usize + 1,
+        };
+        let mut counts = vec![0; bucket(self.max()) + 1];
+        for &size in self.sizes.iter() {
+            counts[bucket(size)] += 1;
+        }
+        let lower_bound = |i: usize| if i == 0 { 0 } else { 1 << (i - 1) };
+
+        counts
+            .into_iter()
+            .enumerate()
+            .skip(bucket(self.min()))
+            .map(|(i, count)| (lower_bound(i), count))
+            .collect()
+    }
+
+    /// Format the summary, histogram and outliers. `max_outliers` limits how many outliers are
+    /// printed.
+    pub fn display_list(&self, root: &JsonSize, json: &str, max_outliers: usize) -> Vec<String> {
+        let pointer = root.path_to_json_pointer(json, &self.path).unwrap();
+        let mut lines = vec![
+            format!(
+                "{:?}: {} items, {} bytes",
+                pointer,
+                self.len(),
+                self.total_size
+            ),
+            format!(
+                "  min {}, median {}, p90 {}, p99 {}, max {}",
+                self.min(),
+                self.percentile(50.0),
+                self.percentile(90.0),
+                self.percentile(99.0),
+                self.max()
+            ),
+        ];
+
+        let histogram = self.histogram();
+        let max_count = histogram.iter().map(|x| x.1).max().unwrap_or(0);
+        for (lower_bound, count) in histogram {
+            let upper_bound = match lower_bound {
+                0 => 0,
+                x => x * 2 - 1,
+            };
+            let bar_len = (count * 40).div_ceil(max_count.max(1));
+            lines.push(format!(
+                "  {:>10}..{:<10} |{:40} {}",
+                lower_bound,
+                upper_bound,
+                "#".repeat(bar_len),
+                count
+            ));
+        }
+
+        if !self.outliers.is_empty() {
+            lines.push(format!("  {} outliers:", self.outliers.len()));
+            for (size, index) in self.outliers.iter().take(max_outliers) {
+                lines.push(format!("  {:12} {}/{}", size, pointer, index));
+            }
+        }
+
+        lines
+    }
+}
+
+fn percentile(sorted: &[usize], p: f64) -> usize {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Distribution of every array below `js` (including `js`) with at least `min_len` items,
+/// biggest array first. `path` is the location of `js`.
+pub fn array_distributions(
+    js: &JsonSize,
+    path: Vec<usize>,
+    min_len: usize,
+    outlier_factor: f64,
+) -> Vec<ArrayDistribution> {
+    let mut arrays = vec![];
+    let mut stack = vec![(path, js)];
+    while let Some((path, node)) = stack.pop() {
+        for (i, child) in node.children().iter().enumerate() {
+            let mut child_path = path.clone();
+            child_path.push(i);
+            stack.push((child_path, child));
+        }
+        if node.value_kind() == JsonValueKind::Array && node.children().len() >= min_len {
+            arrays.push(ArrayDistribution::new(node, path, outlier_factor));
+        }
+    }
+    arrays.sort_by(|a, b| {
+        b.total_size
+            .cmp(&a.total_size)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+
+    arrays
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribution() {
+        let json = r#"[1,22,333,4444,55555,1,1,1,1,1000000000000000000000000000000]"#;
+        let js = JsonSize::new(json);
+        let dist = ArrayDistribution::new(&js, vec![], 10.0);
+
+        assert_eq!(dist.len(), 10);
+        assert_eq!(dist.min(), 1);
+        assert_eq!(dist.max(), 31);
+        assert_eq!(dist.percentile(50.0), 1);
+        assert_eq!(dist.percentile(90.0), 5);
+        assert_eq!(dist.percentile(99.0), 31);
+        assert_eq!(dist.outliers, vec![(31, 9)]);
+        assert_eq!(
+            dist.histogram(),
+            vec![(1, 5), (2, 2), (4, 2), (8, 0), (16, 1)]
+        );
+    }
+
+    #[test]
+    fn test_array_distributions() {
+        let json = r#"{"a": [[1, 2], [3]], "b": [], "c": {"d": ["x", "y"]}}"#;
+        let js = JsonSize::new(json);
+        let paths: Vec<_> = array_distributions(&js, vec![], 2, 10.0)
+            .into_iter()
+            .map(|x| js.path_to_json_pointer(json, &x.path).unwrap())
+            .collect();
+        assert_eq!(paths, vec!["/a", "/c/d", "/a/0"]);
+    }
+}
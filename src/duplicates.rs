@@ -0,0 +1,241 @@
+//! Find values that appear more than once, ignoring whitespace differences, and estimate how
+//! many bytes would be saved by storing them only once.
+use crate::size::{canonical_json_string, size_bar, unescape_json_string, JsonSize, JsonValueKind};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Hash of the minified value, with strings and keys decoded so that `"\u0041"` and `"A"` are
+/// equal. Values with the same id are considered equal, the probability of a false positive with
+/// a 64-bit hash is negligible.
+type ValueId = u64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Parents {
+    None,
+    /// All the occurrences are inside occurrences of the same value
+    Same(ValueId),
+    Mixed,
+}
+
+#[derive(Debug, Clone)]
+struct Group {
+    count: usize,
+    /// Minified size of the first occurrence
+    minified_size: usize,
+    /// Paths of the first occurrences
+    paths: Vec<Vec<usize>>,
+    parents: Parents,
+}
+
+/// A value that appears more than once
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    /// Number of occurrences
+    pub count: usize,
+    /// Size of the first occurrence, minified
+    pub minified_size: usize,
+    /// Paths of child indices of the first occurrences
+    pub paths: Vec<Vec<usize>>,
+}
+
+impl Duplicate {
+    /// Bytes saved if all the occurrences except the first one were replaced by a reference.
+    /// The size of the reference itself is not counted.
+    pub fn savings(&self) -> usize {
+        (self.count - 1) * self.minified_size
+    }
+}
+
+struct Finder<'a> {
+    json: &'a str,
+    min_size: usize,
+    max_paths: usize,
+    groups: HashMap<ValueId, Group>,
+}
+
+impl<'a> Finder<'a> {
+    fn visit(&mut self, js: &JsonSize, path: &mut Vec<usize>) -> ValueId {
+        let mut hasher = DefaultHasher::new();
+        js.value_kind().hash(&mut hasher);
+        let mut children_ids = Vec::with_capacity(js.children().len());
+        if js.value_kind() == JsonValueKind::String {
+            canonical_json_string(&self.json[js.span()]).hash(&mut hasher);
+        } else if js.children().is_empty() {
+            self.json[js.span()].hash(&mut hasher);
+        } else {
+            for (i, child) in js.children().iter().enumerate() {
+                path.push(i);
+                let id = self.visit(child, path);
+                path.pop();
+                match child.key().get_key_str(self.json) {
+                    Some(key) if key.contains('\\') => unescape_json_string(key).hash(&mut hasher),
+                    Some(key) => key.hash(&mut hasher),
+                    None => (),
+                }
+                id.hash(&mut hasher);
+                children_ids.push(id);
+            }
+        }
+        let id = hasher.finish();
+
+        for child_id in children_ids {
+            if let Some(group) = self.groups.get_mut(&child_id) {
+                group.parents = match group.parents {
+                    Parents::None => Parents::Same(id),
+                    Parents::Same(x) if x == id => Parents::Same(id),
+                    _ => Parents::Mixed,
+                };
+            }
+        }
+
+        if js.minified_size() >= self.min_size {
+            let group = self.groups.entry(id).or_insert_with(|| Group {
+                count: 0,
+                minified_size: js.minified_size(),
+                paths: vec![],
+                parents: Parents::None,
+            });
+            group.count += 1;
+            if group.paths.len() < self.max_paths {
+                group.paths.push(path.clone());
+            }
+            if path.is_empty() {
+                // The root has no parent, this can only happen once
+                group.parents = Parents::Mixed;
+            }
+        }
+
+        id
+    }
+}
+
+/// Find values with a minified size of at least `min_size` that appear more than once, most
+/// savings first. Values whose occurrences are all inside occurrences of a bigger duplicate are
+/// not reported, as they are already included in its savings. At most `max_paths` occurrences
+/// are returned for each value.
+pub fn find_duplicates(
+    js: &JsonSize,
+    json: &str,
+    min_size: usize,
+    max_paths: usize,
+) -> Vec<Duplicate> {
+    let mut finder = Finder {
+        json,
+        min_size,
+        max_paths,
+        groups: HashMap::new(),
+    };
+    finder.visit(js, &mut vec![]);
+
+    let groups = &finder.groups;
+    let is_implied = |group: &Group| match group.parents {
+        Parents::Same(parent) => groups
+            .get(&parent)
+            .is_some_and(|parent| parent.count == group.count),
+        _ => false,
+    };
+    let mut duplicates: Vec<Duplicate> = groups
+        .iter()
+        .filter(|(_, group)| group.count > 1 && !is_implied(group))
+        .map(|(_, group)| Duplicate {
+            count: group.count,
+            minified_size: group.minified_size,
+            paths: group.paths.clone(),
+        })
+        .collect();
+    duplicates.sort_by(|a, b| {
+        b.savings()
+            .cmp(&a.savings())
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+
+    duplicates
+}
+
+/// Format as a table, `root` is the tree the paths start from
+pub fn display_list(root: &JsonSize, json: &str, duplicates: &[Duplicate]) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{:>12} {:12} {:>10} {:>10} First occurrences",
+        "Savings", "", "Count", "Size"
+    )];
+    for duplicate in duplicates {
+        let mut pointers: Vec<String> = duplicate
+            .paths
+            .iter()
+            .map(|path| root.path_to_json_pointer(json, path).unwrap())
+            .collect();
+        if duplicate.count > duplicate.paths.len() {
+            pointers.push("...".to_string());
+        }
+        lines.push(format!(
+            "{:12} {} {:10} {:10} {}",
+            duplicate.savings(),
+            size_bar(duplicate.savings(), root.total_size()),
+            duplicate.count,
+            duplicate.minified_size,
+            pointers.join(", ")
+        ));
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pointers(json: &str, duplicate: &Duplicate) -> Vec<String> {
+        let js = JsonSize::new(json);
+        duplicate
+            .paths
+            .iter()
+            .map(|path| js.path_to_json_pointer(json, path).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_whitespace_is_ignored() {
+        let json = r#"[{"a": [1, 2], "b": "x"}, {"a":[1,2],"b":"x"}, {"a": [1, 2], "b": "y"}]"#;
+        let js = JsonSize::new(json);
+        let duplicates = find_duplicates(&js, json, 5, 10);
+
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].count, 2);
+        assert_eq!(duplicates[0].minified_size, r#"{"a":[1,2],"b":"x"}"#.len());
+        assert_eq!(pointers(json, &duplicates[0]), vec!["/0", "/1"]);
+        // Also appears in /2, so it is not implied by the previous one
+        assert_eq!(duplicates[1].count, 3);
+        assert_eq!(duplicates[1].savings(), 2 * "[1,2]".len());
+    }
+
+    #[test]
+    fn test_implied_duplicates_are_hidden() {
+        let json = r#"{"x": {"config": {"a": 1}}, "y": {"config": {"a": 1}}}"#;
+        let js = JsonSize::new(json);
+        let duplicates = find_duplicates(&js, json, 1, 1);
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(pointers(json, &duplicates[0]), vec!["/x"]);
+    }
+
+    #[test]
+    fn test_keys_are_compared() {
+        let json = r#"[{"a": 1}, {"b": 1}, [1], [1]]"#;
+        let js = JsonSize::new(json);
+        let duplicates = find_duplicates(&js, json, 2, 10);
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(pointers(json, &duplicates[0]), vec!["/2", "/3"]);
+    }
+
+    #[test]
+    fn test_escapes_are_decoded() {
+        let json = r#"[{"a": "\u0041BC"}, {"\u0061": "ABC"}]"#;
+        let js = JsonSize::new(json);
+        let duplicates = find_duplicates(&js, json, 1, 10);
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(pointers(json, &duplicates[0]), vec!["/0", "/1"]);
+    }
+}
@@ -0,0 +1,290 @@
+//! Detect and convert the encoding of JSON files. RFC 8259 requires UTF-8, but UTF-16 files with
+//! a byte order mark are common on Windows. Without a BOM the encoding is guessed from the
+//! pattern of zero bytes in the first 4 bytes, because the first two characters of a JSON text
+//! are always ASCII (RFC 4627, section 3).
+use std::fmt;
+
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+}
+
+impl Encoding {
+    /// Encoding and whether the input starts with a byte order mark
+    pub fn detect(bytes: &[u8]) -> (Self, bool) {
+        // The UTF-32LE BOM starts with the UTF-16LE BOM, so it must be checked first
+        for encoding in [
+            Encoding::Utf32Le,
+            Encoding::Utf32Be,
+            Encoding::Utf8,
+            Encoding::Utf16Le,
+            Encoding::Utf16Be,
+        ] {
+            if bytes.starts_with(encoding.bom()) {
+                return (encoding, true);
+            }
+        }
+        let encoding = match bytes {
+            [0, 0, 0, x, ..] if *x != 0 => Encoding::Utf32Be,
+            [x, 0, 0, 0, ..] if *x != 0 => Encoding::Utf32Le,
+            [0, x, 0, y, ..] if *x != 0 && *y != 0 => Encoding::Utf16Be,
+            [x, 0, y, 0, ..] if *x != 0 && *y != 0 => Encoding::Utf16Le,
+            // A single character
+            [0, x] if *x != 0 => Encoding::Utf16Be,
+            [x, 0] if *x != 0 => Encoding::Utf16Le,
+            _ => Encoding::Utf8,
+        };
+
+        (encoding, false)
+    }
+
+    pub fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => &[0xEF, 0xBB, 0xBF],
+            Encoding::Utf16Le => &[0xFF, 0xFE],
+            Encoding::Utf16Be => &[0xFE, 0xFF],
+            Encoding::Utf32Le => &[0xFF, 0xFE, 0x00, 0x00],
+            Encoding::Utf32Be => &[0x00, 0x00, 0xFE, 0xFF],
+        }
+    }
+
+    /// Bytes per code unit: 1, 2 or 4
+    pub fn unit(self) -> usize {
+        match self {
+            Encoding::Utf8 => 1,
+            Encoding::Utf16Le | Encoding::Utf16Be => 2,
+            Encoding::Utf32Le | Encoding::Utf32Be => 4,
+        }
+    }
+
+    /// Bytes used by `c` in this encoding
+    pub fn char_len(self, c: char) -> usize {
+        match self {
+            Encoding::Utf8 => c.len_utf8(),
+            Encoding::Utf16Le | Encoding::Utf16Be => c.len_utf16() * 2,
+            Encoding::Utf32Le | Encoding::Utf32Be => 4,
+        }
+    }
+
+    /// Bytes used by `s` in this encoding, without BOM
+    pub fn encoded_len(self, s: &str) -> usize {
+        match self {
+            Encoding::Utf8 => s.len(),
+            _ => s.chars().map(|c| self.char_len(c)).sum(),
+        }
+    }
+
+    pub fn encode(self, s: &str, bom: bool) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.encoded_len(s) + 4);
+        if bom {
+            bytes.extend_from_slice(self.bom());
+        }
+        match self {
+            Encoding::Utf8 => bytes.extend_from_slice(s.as_bytes()),
+            Encoding::Utf16Le => bytes.extend(s.encode_utf16().flat_map(u16::to_le_bytes)),
+            Encoding::Utf16Be => bytes.extend(s.encode_utf16().flat_map(u16::to_be_bytes)),
+            Encoding::Utf32Le => bytes.extend(s.chars().flat_map(|c| (c as u32).to_le_bytes())),
+            Encoding::Utf32Be => bytes.extend(s.chars().flat_map(|c| (c as u32).to_be_bytes())),
+        }
+
+        bytes
+    }
+
+    /// Replace every non ASCII character with as many `x` as code units it needs in this
+    /// encoding. Parsing the result gives the sizes of `s` measured in code units, because
+    /// whitespace and control characters are always ASCII.
+    pub(crate) fn code_unit_text(self, s: &str) -> String {
+        let mut out = String::with_capacity(s.len());
+        for c in s.chars() {
+            if c.is_ascii() {
+                out.push(c);
+            } else {
+                let units = self.char_len(c) / self.unit();
+                out.extend(std::iter::repeat_n('x', units));
+            }
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+            Encoding::Utf32Le => "utf-32le",
+            Encoding::Utf32Be => "utf-32be",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "utf-16le" | "utf16le" => Ok(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Encoding::Utf16Be),
+            "utf-32le" | "utf32le" => Ok(Encoding::Utf32Le),
+            "utf-32be" | "utf32be" => Ok(Encoding::Utf32Be),
+            _ => Err(format!(
+                "expected \"utf-8\", \"utf-16le\", \"utf-16be\", \"utf-32le\" or \"utf-32be\", got {:?}",
+                s
+            )),
+        }
+    }
+}
+
+/// Text of a file converted to UTF-8, and how it was encoded
+#[derive(Debug, PartialEq, Eq)]
+pub struct Decoded {
+    /// Without the byte order mark
+    pub text: String,
+    pub encoding: Encoding,
+    pub bom: bool,
+}
+
+impl Decoded {
+    /// Size of the original file
+    pub fn encoded_len(&self) -> usize {
+        let bom = if self.bom {
+            self.encoding.bom().len()
+        } else {
+            0
+        };
+        bom + self.encoding.encoded_len(&self.text)
+    }
+
+    /// Byte offset in `text` of the character at byte `offset` of the original file, `None` if
+    /// it is past the end. Offsets inside the byte order mark are the start of the text.
+    pub fn text_offset(&self, offset: usize) -> Option<usize> {
+        let bom = if self.bom {
+            self.encoding.bom().len()
+        } else {
+            0
+        };
+        let mut offset = offset.saturating_sub(bom);
+        for (i, c) in self.text.char_indices() {
+            let len = self.encoding.char_len(c);
+            if offset < len {
+                return Some(i);
+            }
+            offset -= len;
+        }
+
+        None
+    }
+}
+
+pub fn decode(mut bytes: Vec<u8>) -> Result<Decoded, String> {
+    let (encoding, bom) = Encoding::detect(&bytes);
+    if bom {
+        bytes.drain(..encoding.bom().len());
+    }
+    let invalid = || format!("stream did not contain valid {}", encoding);
+
+    let text = match encoding {
+        Encoding::Utf8 => String::from_utf8(bytes).map_err(|_| invalid())?,
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            if !bytes.len().is_multiple_of(2) {
+                return Err(invalid());
+            }
+            let units = bytes.chunks_exact(2).map(|x| match encoding {
+                Encoding::Utf16Le => u16::from_le_bytes([x[0], x[1]]),
+                _ => u16::from_be_bytes([x[0], x[1]]),
+            });
+            char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .map_err(|_| invalid())?
+        }
+        Encoding::Utf32Le | Encoding::Utf32Be => {
+            if !bytes.len().is_multiple_of(4) {
+                return Err(invalid());
+            }
+            bytes
+                .chunks_exact(4)
+                .map(|x| {
+                    let x = [x[0], x[1], x[2], x[3]];
+                    let c = match encoding {
+                        Encoding::Utf32Le => u32::from_le_bytes(x),
+                        _ => u32::from_be_bytes(x),
+                    };
+                    char::from_u32(c)
+                })
+                .collect::<Option<String>>()
+                .ok_or_else(invalid)?
+        }
+    };
+
+    Ok(Decoded {
+        text,
+        encoding,
+        bom,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let json = "{\"a\": \"\u{e9}\u{1f600}\"}";
+        for encoding in [
+            Encoding::Utf8,
+            Encoding::Utf16Le,
+            Encoding::Utf16Be,
+            Encoding::Utf32Le,
+            Encoding::Utf32Be,
+        ] {
+            for bom in [false, true] {
+                let bytes = encoding.encode(json, bom);
+                assert_eq!(Encoding::detect(&bytes), (encoding, bom));
+                let decoded = decode(bytes.clone()).unwrap();
+                assert_eq!(decoded.text, json);
+                assert_eq!(decoded.encoded_len(), bytes.len());
+            }
+        }
+        assert_eq!(Encoding::detect(&[0, b'1']), (Encoding::Utf16Be, false));
+        assert_eq!(Encoding::detect(b"1"), (Encoding::Utf8, false));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(decode(vec![0xFF, 0xFE, b'[']).is_err());
+        // Unpaired surrogate
+        assert!(decode(vec![0xFF, 0xFE, 0x00, 0xD8]).is_err());
+        assert!(decode(vec![b'"', 0xFF, b'"']).is_err());
+    }
+
+    #[test]
+    fn test_code_unit_text() {
+        let s = "\"\u{e9}\u{1f600}\"";
+        assert_eq!(Encoding::Utf8.code_unit_text(s), "\"xxxxxx\"");
+        assert_eq!(Encoding::Utf16Le.code_unit_text(s), "\"xxx\"");
+        assert_eq!(Encoding::Utf32Be.code_unit_text(s), "\"xx\"");
+    }
+
+    #[test]
+    fn test_text_offset() {
+        let json = "[\"\u{e9}\", 1]";
+        let decoded = decode(Encoding::Utf16Le.encode(json, true)).unwrap();
+        // BOM
+        assert_eq!(decoded.text_offset(0), Some(0));
+        assert_eq!(decoded.text_offset(2), Some(0));
+        // Second half of the é
+        assert_eq!(decoded.text_offset(7), Some(2));
+        assert_eq!(decoded.text_offset(8), Some(4));
+        assert_eq!(decoded.text_offset(16), Some(json.len() - 1));
+        assert_eq!(decoded.text_offset(18), None);
+    }
+}
@@ -0,0 +1,116 @@
+//! Shannon entropy of the data bytes of a value, to find content that looks random (hashes,
+//! base64 blobs, encrypted payloads) or that is very redundant.
+use crate::size::{JsonSize, JsonValueKind};
+use std::ops::Range;
+
+/// Values with fewer data bytes are never classified, there are not enough samples
+const MIN_CLASSIFY_BYTES: usize = 256;
+/// Bits per byte. Base64 is close to 6, English text is around 4.5.
+const HIGH_ENTROPY: f64 = 5.5;
+const LOW_ENTROPY: f64 = 2.0;
+
+/// Entropy of the data bytes of a value: the contents of the strings (keys included, without
+/// quotes), numbers and keywords. Whitespace and control characters are ignored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entropy {
+    /// Bits per byte, between 0 and 8
+    pub bits: f64,
+    pub data_bytes: usize,
+}
+
+impl Entropy {
+    pub fn new(js: &JsonSize, json: &str) -> Self {
+        let mut counts = [0usize; 256];
+        count_data_bytes(js, json, &mut counts);
+        let data_bytes: usize = counts.iter().sum();
+        let bits = counts
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / data_bytes as f64;
+                -p * p.log2()
+            })
+            .sum::<f64>()
+            // Avoid printing -0.00
+            .max(0.0);
+
+        Entropy { bits, data_bytes }
+    }
+
+    /// "random" or "redundant" if the value has enough data to tell, empty string otherwise
+    pub fn class(&self) -> &'static str {
+        if self.data_bytes < MIN_CLASSIFY_BYTES {
+            ""
+        } else if self.bits >= HIGH_ENTROPY {
+            "random"
+        } else if self.bits <= LOW_ENTROPY {
+            "redundant"
+        } else {
+            ""
+        }
+    }
+
+    pub fn header() -> String {
+        format!("{:>7} {:9}", "Entropy", "")
+    }
+
+    pub fn to_display(self) -> String {
+        format!("{:7.2} {:9}", self.bits, self.class())
+    }
+}
+
+fn count_data_bytes(js: &JsonSize, json: &str, counts: &mut [usize; 256]) {
+    let mut count = |range: Range<usize>| {
+        for &b in json[range].as_bytes() {
+            counts[b as usize] += 1;
+        }
+    };
+    if let Some(key_span) = js.key().key_span() {
+        count(key_span);
+    }
+    let span = js.span();
+    match js.value_kind() {
+        JsonValueKind::Object | JsonValueKind::Array => {}
+        JsonValueKind::String => count(span.start + 1..span.end - 1),
+        _ => count(span),
+    }
+    for child in js.children() {
+        count_data_bytes(child, json, counts);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entropy() {
+        let json = r#"{"aaaa": "aaaa", "b": [null, 1]}"#;
+        let js = JsonSize::new(json);
+
+        let entropy = Entropy::new(&js.children()[0], json);
+        assert_eq!(entropy.data_bytes, 8);
+        assert_eq!(entropy.bits, 0.0);
+        // "b", "null", "1": 6 bytes, 5 distinct, and "l" twice
+        let entropy = Entropy::new(&js.children()[1], json);
+        assert_eq!(entropy.data_bytes, 6);
+        assert!((entropy.bits - 2.252).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_classes() {
+        let redundant = format!("[\"{}\"]", "ab".repeat(200));
+        let entropy = Entropy::new(&JsonSize::new(&redundant), &redundant);
+        assert_eq!(entropy.bits, 1.0);
+        assert_eq!(entropy.class(), "redundant");
+
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let base64 = format!("\"{}\"", alphabet.repeat(4));
+        let entropy = Entropy::new(&JsonSize::new(&base64), &base64);
+        assert_eq!(entropy.bits, 6.0);
+        assert_eq!(entropy.class(), "random");
+
+        let short = r#""abcdefghijklmnopqrstuvwxyz""#;
+        assert_eq!(Entropy::new(&JsonSize::new(short), short).class(), "");
+    }
+}
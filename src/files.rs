@@ -0,0 +1,461 @@
+//! Sizes of many JSON files at once, like the files inside an archive or a directory.
+use crate::encoding::Decoded;
+use crate::size::{size_bar, Dialect, JsonSize};
+use rayon::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A file that was parsed successfully
+#[derive(Debug)]
+pub struct JsonFile {
+    pub name: String,
+    /// Contents converted to UTF-8
+    pub json: String,
+    /// Sizes in bytes of the original encoding
+    pub js: JsonSize,
+}
+
+impl JsonFile {
+    /// Add the `fields` biggest members of the file, indented by `depth`
+    fn push_members(
+        &self,
+        lines: &mut Vec<String>,
+        depth: usize,
+        total_size: usize,
+        fields: usize,
+    ) {
+        let mut members: Vec<_> = self.js.children().iter().collect();
+        members.sort_by_key(|x| std::cmp::Reverse(x.member_size()));
+        for member in members.into_iter().take(fields) {
+            let name = member.key().to_display(&self.json);
+            lines.push(tree_line(member.member_size(), total_size, depth, &name));
+        }
+    }
+}
+
+/// A line of `display_list`, with the name indented by `depth`
+fn tree_line(size: usize, total_size: usize, depth: usize, name: &str) -> String {
+    format!(
+        "{:12} {} {:indent$}{}",
+        size,
+        size_bar(size, total_size),
+        "",
+        name,
+        indent = depth * 2
+    )
+}
+
+/// A file that looks like JSON, but could not be parsed
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileError {
+    pub name: String,
+    pub error: String,
+}
+
+/// Extension of a file name, ignoring a compression extension like `.gz`
+pub(crate) fn extension_without_compression(name: &str) -> Option<&str> {
+    let name = ["gz", "zst", "bz2", "xz"]
+        .iter()
+        .find_map(|ext| name.strip_suffix(&format!(".{}", ext)))
+        .unwrap_or(name);
+    Path::new(name).extension().and_then(|x| x.to_str())
+}
+
+/// Extensions of files that are expected to contain JSON, or YAML that is converted to JSON
+fn is_json_name(name: &str) -> bool {
+    matches!(
+        extension_without_compression(name),
+        Some("json" | "jsonc" | "json5" | "geojson" | "yaml" | "yml")
+    )
+}
+
+/// Decompress, decode and parse the contents of a file. The dialect is implied by the file
+/// extension.
+pub fn parse_file(name: &str, bytes: Vec<u8>) -> Result<JsonFile, String> {
+    let (bytes, _) = crate::decompress::decompress(bytes).map_err(|e| e.to_string())?;
+    let decoded = crate::encoding::decode(bytes)?;
+    if crate::yaml::is_yaml_path(Path::new(name)) {
+        let yaml = crate::yaml::parse_yaml_decoded(&decoded)?;
+        return Ok(JsonFile {
+            name: name.to_string(),
+            json: yaml.json,
+            js: yaml.js,
+        });
+    }
+    let dialect = Dialect::from_extension(Path::new(name)).unwrap_or_default();
+    let js = parse_decoded(&decoded, dialect)?;
+
+    Ok(JsonFile {
+        name: name.to_string(),
+        json: decoded.text,
+        js,
+    })
+}
+
+/// Parse decoded JSON in `dialect`, with the sizes in bytes of its original encoding
+pub fn parse_decoded(decoded: &Decoded, dialect: Dialect) -> Result<JsonSize, String> {
+    JsonSize::try_with_encoding(&decoded.text, dialect, decoded.encoding, decoded.bom)
+        .ok_or_else(|| "not valid JSON".to_string())
+}
+
+/// The files that contain JSON out of a list of files
+#[derive(Debug, Default)]
+pub struct ParsedFiles {
+    pub files: Vec<JsonFile>,
+    /// Files with a JSON extension that could not be parsed
+    pub errors: Vec<FileError>,
+    /// Number of other files that do not contain JSON
+    pub ignored: usize,
+}
+
+impl ParsedFiles {
+    /// Parse `(name, contents)` pairs in parallel, keeping their order
+    pub fn parse(inputs: Vec<(String, Vec<u8>)>) -> Self {
+        let results: Vec<_> = inputs
+            .into_par_iter()
+            .map(|(name, bytes)| {
+                let result = parse_file(&name, bytes);
+                (name, result)
+            })
+            .collect();
+        let mut parsed = ParsedFiles::default();
+        for (name, result) in results {
+            match result {
+                Ok(file) => parsed.files.push(file),
+                Err(error) if is_json_name(&name) => parsed.errors.push(FileError { name, error }),
+                Err(_) => parsed.ignored += 1,
+            }
+        }
+
+        parsed
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.files.iter().map(|x| x.js.total_size()).sum()
+    }
+
+    /// Format like `JsonSize::display_list`, with one line per file and the `fields` biggest
+    /// members of each file below it, followed by the files that could not be parsed
+    pub fn display_list(&self, sort_by_size: bool, fields: usize) -> Vec<String> {
+        let total_size = self.total_size();
+        let mut lines = vec![format!("{:12} [##########] Total", total_size)];
+        let mut files: Vec<_> = self.files.iter().collect();
+        if sort_by_size {
+            files.sort_by_key(|x| std::cmp::Reverse(x.js.total_size()));
+        }
+        for file in files {
+            lines.push(tree_line(file.js.total_size(), total_size, 0, &file.name));
+            file.push_members(&mut lines, 1, total_size, fields);
+        }
+        self.push_summary(&mut lines);
+
+        lines
+    }
+
+    /// Files that could not be parsed, and number of ignored files
+    fn push_summary(&self, lines: &mut Vec<String>) {
+        if !self.errors.is_empty() {
+            lines.push(format!("{} files could not be parsed:", self.errors.len()));
+            for error in self.errors.iter() {
+                lines.push(format!("  {}: {}", error.name, error.error));
+            }
+        }
+        if self.ignored > 0 {
+            lines.push(format!(
+                "{} files that are not JSON were ignored",
+                self.ignored
+            ));
+        }
+    }
+}
+
+/// A directory or a JSON file, given as input or found inside a directory
+#[derive(Debug)]
+pub struct FileTree {
+    /// The path as given for inputs, the file name for the rest
+    pub name: String,
+    /// Sum of the sizes of the JSON files below
+    pub size: usize,
+    pub kind: FileTreeKind,
+}
+
+#[derive(Debug)]
+pub enum FileTreeKind {
+    Directory(Vec<FileTree>),
+    /// Index in `ParsedFiles::files`
+    File(usize),
+}
+
+/// Combined size tree of many files and directories, like `du`. Each file's `JsonSize` hangs
+/// below its entry.
+#[derive(Debug)]
+pub struct DirectoryTree {
+    pub entries: Vec<FileTree>,
+    pub parsed: ParsedFiles,
+}
+
+/// Walk `path` adding the files to read to `paths`. Inside directories only files with a JSON
+/// extension are read, and symlinks are not followed. Returns `FileTreeKind::File` with an
+/// index into `paths`.
+fn scan(
+    path: &Path,
+    name: String,
+    is_input: bool,
+    paths: &mut Vec<PathBuf>,
+    parsed: &mut ParsedFiles,
+) -> Option<FileTree> {
+    let metadata = if is_input {
+        fs::metadata(path)
+    } else {
+        fs::symlink_metadata(path)
+    };
+    let is_dir = metadata.as_ref().map(|x| x.is_dir()).unwrap_or(false);
+    if !is_dir {
+        if metadata.is_ok_and(|x| !x.is_file()) {
+            return None;
+        }
+        if !is_input && !is_json_name(&name) {
+            parsed.ignored += 1;
+            return None;
+        }
+        // Read errors of inputs are reported after reading
+        paths.push(path.to_path_buf());
+        return Some(FileTree {
+            name,
+            size: 0,
+            kind: FileTreeKind::File(paths.len() - 1),
+        });
+    }
+
+    let mut entries = match fs::read_dir(path).and_then(|x| x.collect::<Result<Vec<_>, _>>()) {
+        Ok(entries) => entries,
+        Err(e) => {
+            parsed.errors.push(FileError {
+                name: path.display().to_string(),
+                error: e.to_string(),
+            });
+            return None;
+        }
+    };
+    entries.sort_by_key(|x| x.file_name());
+    let children = entries
+        .into_iter()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            scan(&entry.path(), name, false, paths, parsed)
+        })
+        .collect();
+    let name = if name.ends_with('/') {
+        name
+    } else {
+        format!("{}/", name)
+    };
+
+    Some(FileTree {
+        name,
+        size: 0,
+        kind: FileTreeKind::Directory(children),
+    })
+}
+
+/// Replace the indices into the scanned paths with indices into the parsed files, remove the
+/// files that could not be parsed and empty directories, and compute the sizes
+fn prune(mut tree: FileTree, indices: &[Option<usize>], files: &[JsonFile]) -> Option<FileTree> {
+    match tree.kind {
+        FileTreeKind::File(i) => {
+            let index = indices[i]?;
+            tree.size = files[index].js.total_size();
+            tree.kind = FileTreeKind::File(index);
+        }
+        FileTreeKind::Directory(children) => {
+            let children: Vec<_> = children
+                .into_iter()
+                .filter_map(|x| prune(x, indices, files))
+                .collect();
+            if children.is_empty() {
+                return None;
+            }
+            tree.size = children.iter().map(|x| x.size).sum();
+            tree.kind = FileTreeKind::Directory(children);
+        }
+    }
+
+    Some(tree)
+}
+
+impl DirectoryTree {
+    /// Read and parse in parallel every file of `paths` and the JSON files inside the
+    /// directories of `paths`
+    pub fn read(paths: &[PathBuf]) -> Self {
+        let mut scanned = vec![];
+        let mut parsed = ParsedFiles::default();
+        let entries: Vec<_> = paths
+            .iter()
+            .filter_map(|path| {
+                let name = path.display().to_string();
+                scan(path, name, true, &mut scanned, &mut parsed)
+            })
+            .collect();
+
+        let results: Vec<_> = scanned
+            .par_iter()
+            .map(|path| {
+                let name = path.display().to_string();
+                let result = fs::read(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|bytes| parse_file(&name, bytes));
+                (name, result)
+            })
+            .collect();
+        let mut indices = vec![];
+        for (name, result) in results {
+            match result {
+                Ok(file) => {
+                    indices.push(Some(parsed.files.len()));
+                    parsed.files.push(file);
+                }
+                Err(error) => {
+                    indices.push(None);
+                    parsed.errors.push(FileError { name, error });
+                }
+            }
+        }
+        let entries = entries
+            .into_iter()
+            .filter_map(|x| prune(x, &indices, &parsed.files))
+            .collect();
+
+        DirectoryTree { entries, parsed }
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.entries.iter().map(|x| x.size).sum()
+    }
+
+    /// Format like `JsonSize::display_list`, with directories and files indented by depth and
+    /// the `fields` biggest members of each file below it, followed by the files that could not
+    /// be parsed
+    pub fn display_list(&self, sort_by_size: bool, fields: usize) -> Vec<String> {
+        let total_size = self.total_size();
+        let mut lines = vec![format!("{:12} [##########] Total", total_size)];
+        for entry in sorted(&self.entries, sort_by_size) {
+            self.push_tree(&mut lines, entry, 0, total_size, sort_by_size, fields);
+        }
+        self.parsed.push_summary(&mut lines);
+
+        lines
+    }
+
+    fn push_tree(
+        &self,
+        lines: &mut Vec<String>,
+        tree: &FileTree,
+        depth: usize,
+        total_size: usize,
+        sort_by_size: bool,
+        fields: usize,
+    ) {
+        lines.push(tree_line(tree.size, total_size, depth, &tree.name));
+        match &tree.kind {
+            FileTreeKind::Directory(children) => {
+                for child in sorted(children, sort_by_size) {
+                    self.push_tree(lines, child, depth + 1, total_size, sort_by_size, fields);
+                }
+            }
+            FileTreeKind::File(index) => {
+                self.parsed.files[*index].push_members(lines, depth + 1, total_size, fields);
+            }
+        }
+    }
+}
+
+fn sorted(entries: &[FileTree], sort_by_size: bool) -> Vec<&FileTree> {
+    let mut entries: Vec<_> = entries.iter().collect();
+    if sort_by_size {
+        entries.sort_by_key(|x| std::cmp::Reverse(x.size));
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_files() {
+        let inputs = vec![
+            ("a.json".to_string(), b"[1, 2]".to_vec()),
+            ("README.md".to_string(), b"# Readme".to_vec()),
+            ("broken.json".to_string(), b"{\"a\": ".to_vec()),
+            ("b.jsonc".to_string(), b"{\"b\": 1, // one\n}".to_vec()),
+            (
+                "c.json.gz".to_string(),
+                crate::decompress::Compression::Gzip
+                    .compress(b"\"abc\"")
+                    .unwrap(),
+            ),
+        ];
+        let parsed = ParsedFiles::parse(inputs);
+        let names: Vec<_> = parsed.files.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, vec!["a.json", "b.jsonc", "c.json.gz"]);
+        assert_eq!(
+            parsed.errors,
+            vec![FileError {
+                name: "broken.json".to_string(),
+                error: "not valid JSON".to_string()
+            }]
+        );
+        assert_eq!(parsed.ignored, 1);
+        assert_eq!(parsed.total_size(), 6 + 17 + 5);
+        assert_eq!(
+            parsed.display_list(true, 1)[..4],
+            [
+                format!("{:12} [##########] Total", 28),
+                format!("{:12} [#######   ] b.jsonc", 17),
+                format!("{:12} [######    ]   \"b\"", 15),
+                format!("{:12} [###       ] a.json", 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_decoded() {
+        let decoded = crate::encoding::decode(b"{a: 1, // c\n}".to_vec()).unwrap();
+        let js = parse_decoded(&decoded, Dialect::Json5).unwrap();
+        assert_eq!(js.total_size(), decoded.text.len());
+        assert!(parse_decoded(&decoded, Dialect::Json).is_err());
+        assert_eq!(
+            Dialect::from_extension(Path::new("a.json5.gz")),
+            Some(Dialect::Json5)
+        );
+    }
+
+    #[test]
+    fn test_directory_tree() {
+        let dir = std::env::temp_dir().join(format!("jsdu-files-{}", std::process::id()));
+        fs::create_dir_all(dir.join("data/empty")).unwrap();
+        fs::write(dir.join("data/users.json"), r#"{"users": [1, 2], "n": 2}"#).unwrap();
+        fs::write(dir.join("data/broken.json"), "[1, ").unwrap();
+        fs::write(dir.join("data/notes.txt"), "[1]").unwrap();
+        fs::write(dir.join("config.json"), "[true]").unwrap();
+
+        let tree = DirectoryTree::read(&[dir.clone(), dir.join("missing.json")]);
+        assert_eq!(tree.total_size(), 25 + 6);
+        assert_eq!(tree.parsed.errors.len(), 2);
+        assert_eq!(tree.parsed.ignored, 1);
+        let lines = tree.display_list(true, 1);
+        assert_eq!(
+            lines[1..5],
+            [
+                format!("{:12} [##########] {}/", 31, dir.display()),
+                format!("{:12} [######### ]   data/", 25),
+                format!("{:12} [######### ]     users.json", 25),
+                format!("{:12} [######    ]       \"users\"", 16),
+            ]
+        );
+        assert_eq!(lines[5], format!("{:12} [##        ]   config.json", 6));
+        assert_eq!(lines[6], format!("{:12} [##        ]     0", 4));
+        assert_eq!(lines[7], "2 files could not be parsed:");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,335 @@
+//! Tail a growing newline delimited JSON file, like `tail -f`, and keep size statistics of the
+//! records appended to it.
+use crate::ndjson::{Record, RecordReader, RecordStats};
+use crate::schema::SchemaStats;
+use crate::size::{size_bar, Accounting};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Bytes read by one call to `Tail::poll`, so a big file is not read into memory at once
+const CHUNK_SIZE: usize = 16 << 20;
+
+/// Reads the complete lines appended to a file since the last call to `poll`
+#[derive(Debug)]
+pub struct Tail {
+    path: PathBuf,
+    file: Option<File>,
+    /// Identifies the file, to detect when it is replaced by a new one
+    #[cfg(unix)]
+    inode: u64,
+    /// Bytes of the file read so far
+    offset: u64,
+    /// Last line of the file, without newline yet
+    pending: Vec<u8>,
+    /// Most bytes read by one call to `poll`
+    chunk_size: usize,
+    /// The last call to `poll` stopped before the end of the file
+    has_more: bool,
+    /// Records and lines returned so far
+    index: usize,
+    line: usize,
+}
+
+impl Tail {
+    /// Start reading at the end of the file, or at the start if `from_start` is true
+    pub fn new(path: &Path, from_start: bool) -> io::Result<Self> {
+        let mut tail = Tail {
+            path: path.to_path_buf(),
+            file: None,
+            #[cfg(unix)]
+            inode: 0,
+            offset: 0,
+            pending: vec![],
+            chunk_size: CHUNK_SIZE,
+            has_more: false,
+            index: 0,
+            line: 1,
+        };
+        tail.open()?;
+        if !from_start {
+            tail.offset = tail.file.as_ref().unwrap().metadata()?.len();
+        }
+
+        Ok(tail)
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        let file = File::open(&self.path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            self.inode = file.metadata()?.ino();
+        }
+        self.file = Some(file);
+        self.offset = 0;
+        self.line = 1;
+        self.pending.clear();
+
+        Ok(())
+    }
+
+    fn check_replaced(&self) -> io::Result<Replaced> {
+        let metadata = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // Rotated but the new file does not exist yet, keep reading the old one
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Replaced::No),
+            Err(e) => return Err(e),
+        };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            if metadata.ino() != self.inode {
+                return Ok(Replaced::Rotated);
+            }
+        }
+        if metadata.len() < self.offset {
+            return Ok(Replaced::Truncated);
+        }
+
+        Ok(Replaced::No)
+    }
+
+    /// There is more data to read, the next `poll` does not need to wait
+    pub fn has_more(&self) -> bool {
+        self.has_more
+    }
+
+    /// New complete records, reading at most `CHUNK_SIZE` bytes. If the file was truncated or
+    /// rotated, it is read again from the start.
+    pub fn poll(&mut self) -> io::Result<Vec<Record>> {
+        let mut records = vec![];
+        match self.check_replaced()? {
+            Replaced::No => {}
+            Replaced::Truncated => self.open()?,
+            Replaced::Rotated => {
+                // The old file may still have some lines at the end
+                let mut data = std::mem::take(&mut self.pending);
+                self.read_appended(&mut data)?;
+                if self.has_more {
+                    return self.complete_records(data);
+                }
+                if !data.is_empty() {
+                    data.push(b'\n');
+                }
+                records = self.split_records(&data)?;
+                self.open()?;
+            }
+        }
+
+        let mut data = std::mem::take(&mut self.pending);
+        self.read_appended(&mut data)?;
+        records.extend(self.complete_records(data)?);
+
+        Ok(records)
+    }
+
+    /// Records of the complete lines of `data`, the last line is kept until its newline
+    fn complete_records(&mut self, mut data: Vec<u8>) -> io::Result<Vec<Record>> {
+        let end = data.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        self.pending = data.split_off(end);
+        self.split_records(&data)
+    }
+
+    fn split_records(&mut self, data: &[u8]) -> io::Result<Vec<Record>> {
+        let mut records = vec![];
+        for record in RecordReader::new(data) {
+            let mut record = record?;
+            record.index += self.index;
+            record.line += self.line - 1;
+            records.push(record);
+        }
+        self.index += records.len();
+        self.line += data.iter().filter(|&&b| b == b'\n').count();
+
+        Ok(records)
+    }
+
+    fn read_appended(&mut self, data: &mut Vec<u8>) -> io::Result<()> {
+        let file = self.file.as_mut().unwrap();
+        file.seek(SeekFrom::Start(self.offset))?;
+        let read = file.take(self.chunk_size as u64).read_to_end(data)?;
+        self.offset += read as u64;
+        self.has_more = read == self.chunk_size;
+
+        Ok(())
+    }
+}
+
+enum Replaced {
+    No,
+    Truncated,
+    /// The path points to a different file
+    Rotated,
+}
+
+/// Per path sizes of all the records, and how fast each path grows
+#[derive(Debug)]
+pub struct FollowStats {
+    schema: SchemaStats,
+    /// Total of each path at the previous call to `display_list`
+    previous: HashMap<String, usize>,
+    /// Records since the previous call to `display_list`
+    recent: RecordStats,
+    max_records: usize,
+    pub records: usize,
+    pub bytes: usize,
+    recent_bytes: usize,
+}
+
+impl FollowStats {
+    /// Keep the `max_records` largest recent records
+    pub fn new(accounting: Accounting, max_records: usize) -> Self {
+        FollowStats {
+            schema: SchemaStats::new(accounting),
+            previous: HashMap::new(),
+            recent: RecordStats::new(max_records),
+            max_records,
+            records: 0,
+            bytes: 0,
+            recent_bytes: 0,
+        }
+    }
+
+    pub fn add_record(&mut self, record: &Record) {
+        self.records += 1;
+        self.bytes += record.bytes.len();
+        self.recent_bytes += record.bytes.len();
+        if let Some((js, json)) = self.recent.add_record(record) {
+            self.schema.add(&js, json);
+        }
+    }
+
+    /// Format the paths that grew the most during `elapsed`, and the largest records since the
+    /// previous call. Shows at most `limit` paths.
+    pub fn display_list(&mut self, elapsed: Duration, limit: usize) -> Vec<String> {
+        let seconds = elapsed.as_secs_f64().max(0.001);
+        let sorted = self.schema.sorted();
+        let mut rates: Vec<_> = sorted
+            .iter()
+            .map(|(path, stats)| {
+                let previous = self.previous.get(*path).copied().unwrap_or(0);
+                let rate = (stats.total - previous) as f64 / seconds;
+                (rate, *path, stats.total)
+            })
+            .collect();
+        rates.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+
+        let total_rate = self.recent_bytes as f64 / seconds;
+        let mut lines = vec![
+            format!(
+                "{} records, {} bytes, {:.0} bytes/s",
+                self.records, self.bytes, total_rate
+            ),
+            format!("{:>12} {:12} {:>12} Path", "Bytes/s", "", "Total"),
+        ];
+        for (rate, path, total) in rates.iter().take(limit) {
+            lines.push(format!(
+                "{:12.0} {} {:12} {}",
+                rate,
+                size_bar(*rate as usize, total_rate as usize),
+                total,
+                path
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push("Largest recent records:".to_string());
+        lines.extend(self.recent.display_list(self.recent_bytes));
+
+        self.previous = sorted
+            .into_iter()
+            .map(|(path, stats)| (path.to_string(), stats.total))
+            .collect();
+        self.recent = RecordStats::new(self.max_records);
+        self.recent_bytes = 0;
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn texts(records: Vec<Record>) -> Vec<(usize, usize, String)> {
+        records
+            .into_iter()
+            .map(|x| (x.index, x.line, x.text().unwrap().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_tail() {
+        let path =
+            std::env::temp_dir().join(format!("jsdu-test-tail-{}.jsonl", std::process::id()));
+        std::fs::write(&path, "{\"old\": 1}\n").unwrap();
+        let mut tail = Tail::new(&path, false).unwrap();
+        assert_eq!(texts(tail.poll().unwrap()), vec![]);
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"{\"a\": 1}\n{\"b\":").unwrap();
+        assert_eq!(
+            texts(tail.poll().unwrap()),
+            vec![(0, 1, "{\"a\": 1}".to_string())]
+        );
+        file.write_all(b" 2}\n\n[]\n").unwrap();
+        assert_eq!(
+            texts(tail.poll().unwrap()),
+            vec![(1, 2, "{\"b\": 2}".to_string()), (2, 4, "[]".to_string())]
+        );
+
+        // Truncated: read again from the start
+        std::fs::write(&path, "1\n").unwrap();
+        assert_eq!(texts(tail.poll().unwrap()), vec![(3, 1, "1".to_string())]);
+
+        // Read in chunks
+        std::fs::write(&path, "[1]\n[22]\n").unwrap();
+        let mut tail = Tail::new(&path, true).unwrap();
+        tail.chunk_size = 6;
+        assert_eq!(texts(tail.poll().unwrap()), vec![(0, 1, "[1]".to_string())]);
+        assert!(tail.has_more());
+        assert_eq!(
+            texts(tail.poll().unwrap()),
+            vec![(1, 2, "[22]".to_string())]
+        );
+        assert!(!tail.has_more());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_follow_stats() {
+        let mut stats = FollowStats::new(Accounting::Value, 5);
+        for (index, text) in [r#"{"a": "xxxx"}"#, r#"{"a": "x", "b": 1}"#]
+            .iter()
+            .enumerate()
+        {
+            stats.add_record(&Record {
+                index,
+                offset: 0,
+                line: index + 1,
+                bytes: text.as_bytes().to_vec(),
+            });
+        }
+        let lines = stats.display_list(Duration::from_secs(2), 10);
+        assert_eq!(lines[0], "2 records, 31 bytes, 16 bytes/s");
+        assert!(lines[2].ends_with("/a"));
+        // " \"xxxx\"" and " \"x\"" in 2 seconds
+        assert!(
+            lines[2].starts_with(&format!("{:12.0} ", 5.5)),
+            "{}",
+            lines[2]
+        );
+
+        // Nothing new
+        let lines = stats.display_list(Duration::from_secs(1), 10);
+        assert_eq!(lines[0], "2 records, 31 bytes, 0 bytes/s");
+    }
+}
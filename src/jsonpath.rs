@@ -0,0 +1,879 @@
+//! JSONPath queries (RFC 9535) evaluated over a `JsonSize` tree.
+//!
+//! Supports the whole query syntax except function extensions: name, wildcard, index and slice
+//! selectors, descendant segments, and filters that compare values or test for existence.
+use crate::minify::minify_dialect;
+use crate::size::{
+    percent_of, size_bar, unescape_json_string, DisplayOptions, JsonSize, JsonValueKind,
+};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    /// `.name`, `.*`, or `[selectors]`
+    Child(Vec<Selector>),
+    /// `..name`, `..*`, or `..[selectors]`
+    Descendant(Vec<Selector>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice {
+        start: Option<i64>,
+        end: Option<i64>,
+        step: Option<i64>,
+    },
+    Filter(LogicalExpr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum LogicalExpr {
+    Or(Vec<LogicalExpr>),
+    And(Vec<LogicalExpr>),
+    Not(Box<LogicalExpr>),
+    Comparison(Comparable, CmpOp, Comparable),
+    /// Existence test, true if the query selects at least one node
+    Test(Query),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Comparable {
+    Literal(Literal),
+    /// Singular query, selects at most one node
+    Query(Query),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Query {
+    /// Starts at the current node `@` instead of the root `$`
+    relative: bool,
+    segments: Vec<Segment>,
+}
+
+impl Query {
+    fn is_singular(&self) -> bool {
+        self.segments.iter().all(|segment| match segment {
+            Segment::Child(selectors) => {
+                matches!(selectors[..], [Selector::Name(_)] | [Selector::Index(_)])
+            }
+            Segment::Descendant(_) => false,
+        })
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { s, pos: 0 };
+        parser.expect('$')?;
+        let segments = parser.parse_segments()?;
+        if parser.pos != s.len() {
+            return Err(parser.error("unexpected character"));
+        }
+
+        Ok(JsonPath { segments })
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &str) -> String {
+        format!("invalid JSONPath at position {}: {}", self.pos, msg)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, prefix: &str) -> bool {
+        if self.rest().starts_with(prefix) {
+            self.pos += prefix.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {:?}", c)))
+        }
+    }
+
+    fn skip_blank(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.bump();
+        }
+    }
+
+    fn parse_segments(&mut self) -> Result<Vec<Segment>, String> {
+        let mut segments = vec![];
+        loop {
+            // Blank space is allowed between segments, but it may also come before an operator
+            let before_blank = self.pos;
+            self.skip_blank();
+            if self.eat("..") {
+                let selectors = match self.peek() {
+                    Some('[') => self.parse_bracketed_selection()?,
+                    Some('*') => {
+                        self.bump();
+                        vec![Selector::Wildcard]
+                    }
+                    _ => vec![Selector::Name(self.parse_member_name_shorthand()?)],
+                };
+                segments.push(Segment::Descendant(selectors));
+            } else if self.eat(".") {
+                let selectors = if self.eat("*") {
+                    vec![Selector::Wildcard]
+                } else {
+                    vec![Selector::Name(self.parse_member_name_shorthand()?)]
+                };
+                segments.push(Segment::Child(selectors));
+            } else if self.peek() == Some('[') {
+                segments.push(Segment::Child(self.parse_bracketed_selection()?));
+            } else {
+                self.pos = before_blank;
+                return Ok(segments);
+            }
+        }
+    }
+
+    fn parse_member_name_shorthand(&mut self) -> Result<String, String> {
+        let is_first = |c: char| c.is_ascii_alphabetic() || c == '_' || !c.is_ascii();
+        let start = self.pos;
+        match self.peek() {
+            Some(c) if is_first(c) => self.bump(),
+            _ => return Err(self.error("expected member name")),
+        };
+        while let Some(c) = self.peek() {
+            if is_first(c) || c.is_ascii_digit() {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+
+        Ok(self.s[start..self.pos].to_string())
+    }
+
+    fn parse_bracketed_selection(&mut self) -> Result<Vec<Selector>, String> {
+        self.expect('[')?;
+        let mut selectors = vec![];
+        loop {
+            self.skip_blank();
+            selectors.push(self.parse_selector()?);
+            self.skip_blank();
+            match self.bump() {
+                Some(',') => continue,
+                Some(']') => return Ok(selectors),
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_selector(&mut self) -> Result<Selector, String> {
+        match self.peek() {
+            Some('\'' | '"') => Ok(Selector::Name(self.parse_string_literal()?)),
+            Some('*') => {
+                self.bump();
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.bump();
+                self.skip_blank();
+                Ok(Selector::Filter(self.parse_logical_or()?))
+            }
+            _ => {
+                let start = self.parse_optional_int()?;
+                self.skip_blank();
+                if !self.eat(":") {
+                    return start
+                        .map(Selector::Index)
+                        .ok_or_else(|| self.error("expected selector"));
+                }
+                self.skip_blank();
+                let end = self.parse_optional_int()?;
+                self.skip_blank();
+                let step = if self.eat(":") {
+                    self.skip_blank();
+                    self.parse_optional_int()?
+                } else {
+                    None
+                };
+                Ok(Selector::Slice { start, end, step })
+            }
+        }
+    }
+
+    fn parse_optional_int(&mut self) -> Result<Option<i64>, String> {
+        let start = self.pos;
+        self.eat("-");
+        let digits_start = self.pos;
+        while let Some('0'..='9') = self.peek() {
+            self.bump();
+        }
+        let digits = &self.s[digits_start..self.pos];
+        if digits.is_empty() {
+            if self.pos != start {
+                return Err(self.error("expected digits"));
+            }
+            return Ok(None);
+        }
+        if (digits.starts_with('0') && digits.len() > 1) || &self.s[start..self.pos] == "-0" {
+            return Err(self.error("invalid integer"));
+        }
+        self.s[start..self.pos]
+            .parse()
+            .map(Some)
+            .map_err(|_| self.error("integer out of range"))
+    }
+
+    fn parse_string_literal(&mut self) -> Result<String, String> {
+        let quote = self.bump().unwrap();
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error("unterminated string")),
+                Some(c) if c == quote => return Ok(out),
+                Some('\\') => match self.bump() {
+                    Some(c) if c == quote => out.push(c),
+                    Some(c @ ('\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' | 'u')) => {
+                        // Reuse the JSON decoder for the other escape sequences
+                        let escape_start = self.pos - 2;
+                        if c == 'u' {
+                            for _ in 0..4 {
+                                self.bump();
+                            }
+                            if self.rest().starts_with("\\u") {
+                                for _ in 0..6 {
+                                    self.bump();
+                                }
+                            }
+                        }
+                        out.push_str(&unescape_json_string(&self.s[escape_start..self.pos]));
+                    }
+                    _ => return Err(self.error("invalid escape sequence")),
+                },
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn parse_logical_or(&mut self) -> Result<LogicalExpr, String> {
+        let mut exprs = vec![self.parse_logical_and()?];
+        loop {
+            self.skip_blank();
+            if !self.eat("||") {
+                break;
+            }
+            self.skip_blank();
+            exprs.push(self.parse_logical_and()?);
+        }
+
+        Ok(if exprs.len() == 1 {
+            exprs.pop().unwrap()
+        } else {
+            LogicalExpr::Or(exprs)
+        })
+    }
+
+    fn parse_logical_and(&mut self) -> Result<LogicalExpr, String> {
+        let mut exprs = vec![self.parse_basic_expr()?];
+        loop {
+            self.skip_blank();
+            if !self.eat("&&") {
+                break;
+            }
+            self.skip_blank();
+            exprs.push(self.parse_basic_expr()?);
+        }
+
+        Ok(if exprs.len() == 1 {
+            exprs.pop().unwrap()
+        } else {
+            LogicalExpr::And(exprs)
+        })
+    }
+
+    fn parse_basic_expr(&mut self) -> Result<LogicalExpr, String> {
+        if self.eat("!") {
+            self.skip_blank();
+            let expr = if self.peek() == Some('(') {
+                self.parse_paren_expr()?
+            } else {
+                LogicalExpr::Test(self.parse_query()?)
+            };
+            return Ok(LogicalExpr::Not(Box::new(expr)));
+        }
+        if self.peek() == Some('(') {
+            return self.parse_paren_expr();
+        }
+
+        let left = self.parse_comparable()?;
+        let before_blank = self.pos;
+        self.skip_blank();
+        let op = if self.eat("==") {
+            CmpOp::Eq
+        } else if self.eat("!=") {
+            CmpOp::Ne
+        } else if self.eat("<=") {
+            CmpOp::Le
+        } else if self.eat(">=") {
+            CmpOp::Ge
+        } else if self.eat("<") {
+            CmpOp::Lt
+        } else if self.eat(">") {
+            CmpOp::Gt
+        } else {
+            self.pos = before_blank;
+            return match left {
+                Comparable::Query(query) => Ok(LogicalExpr::Test(query)),
+                Comparable::Literal(_) => Err(self.error("expected comparison operator")),
+            };
+        };
+        self.skip_blank();
+        let right = self.parse_comparable()?;
+
+        for side in [&left, &right] {
+            if let Comparable::Query(query) = side {
+                if !query.is_singular() {
+                    return Err(self.error("only singular queries can be compared"));
+                }
+            }
+        }
+
+        Ok(LogicalExpr::Comparison(left, op, right))
+    }
+
+    fn parse_paren_expr(&mut self) -> Result<LogicalExpr, String> {
+        self.expect('(')?;
+        self.skip_blank();
+        let expr = self.parse_logical_or()?;
+        self.skip_blank();
+        self.expect(')')?;
+
+        Ok(expr)
+    }
+
+    fn parse_query(&mut self) -> Result<Query, String> {
+        let relative = match self.bump() {
+            Some('@') => true,
+            Some('$') => false,
+            _ => return Err(self.error("expected '@' or '$'")),
+        };
+        let segments = self.parse_segments()?;
+
+        Ok(Query { relative, segments })
+    }
+
+    fn parse_comparable(&mut self) -> Result<Comparable, String> {
+        let literal = match self.peek() {
+            Some('@' | '$') => return Ok(Comparable::Query(self.parse_query()?)),
+            Some('\'' | '"') => Literal::String(self.parse_string_literal()?),
+            Some('-' | '0'..='9') => Literal::Number(self.parse_number()?),
+            _ if self.eat("true") => Literal::Bool(true),
+            _ if self.eat("false") => Literal::Bool(false),
+            _ if self.eat("null") => Literal::Null,
+            Some(c) if c.is_ascii_lowercase() => {
+                return Err(self.error("function extensions are not supported"))
+            }
+            _ => return Err(self.error("expected literal or query")),
+        };
+
+        Ok(Comparable::Literal(literal))
+    }
+
+    fn parse_number(&mut self) -> Result<f64, String> {
+        let start = self.pos;
+        self.eat("-");
+        while let Some('0'..='9' | '.' | 'e' | 'E') = self.peek() {
+            let c = self.bump();
+            if let Some('e' | 'E') = c {
+                let _ = self.eat("+") || self.eat("-");
+            }
+        }
+        self.s[start..self.pos]
+            .parse()
+            .map_err(|_| self.error("invalid number"))
+    }
+}
+
+/// A node selected by a query: path of child indices from the root, and the node itself
+type Node<'a> = (Vec<usize>, &'a JsonSize);
+
+struct Context<'a> {
+    root: &'a JsonSize,
+    json: &'a str,
+}
+
+impl JsonPath {
+    /// Evaluate the query, returning the path of child indices of every selected node
+    pub fn evaluate(&self, root: &JsonSize, json: &str) -> Vec<Vec<usize>> {
+        let ctx = Context { root, json };
+        ctx.eval_segments(&self.segments, (vec![], root))
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect()
+    }
+}
+
+impl<'a> Context<'a> {
+    fn eval_segments(&self, segments: &[Segment], start: Node<'a>) -> Vec<Node<'a>> {
+        let mut nodes = vec![start];
+        for segment in segments {
+            let mut next = vec![];
+            for node in nodes {
+                match segment {
+                    Segment::Child(selectors) => {
+                        for selector in selectors {
+                            self.select(selector, &node, &mut next);
+                        }
+                    }
+                    Segment::Descendant(selectors) => {
+                        for descendant in descendants(node) {
+                            for selector in selectors {
+                                self.select(selector, &descendant, &mut next);
+                            }
+                        }
+                    }
+                }
+            }
+            nodes = next;
+        }
+
+        nodes
+    }
+
+    fn select(&self, selector: &Selector, (path, node): &Node<'a>, out: &mut Vec<Node<'a>>) {
+        let children = node.children();
+        let mut push = |i: usize| {
+            let mut child_path = path.clone();
+            child_path.push(i);
+            out.push((child_path, &children[i]));
+        };
+        match (selector, node.value_kind()) {
+            (Selector::Name(name), JsonValueKind::Object) => {
+                if let Some(i) = children
+                    .iter()
+                    .position(|child| child.key().matches_token(self.json, name))
+                {
+                    push(i);
+                }
+            }
+            (Selector::Wildcard, JsonValueKind::Object | JsonValueKind::Array) => {
+                (0..children.len()).for_each(push);
+            }
+            (Selector::Index(index), JsonValueKind::Array) => {
+                if let Some(i) = normalize_index(*index, children.len()) {
+                    push(i);
+                }
+            }
+            (Selector::Slice { start, end, step }, JsonValueKind::Array) => {
+                slice_indices(*start, *end, *step, children.len()).for_each(push);
+            }
+            (Selector::Filter(expr), JsonValueKind::Object | JsonValueKind::Array) => {
+                for (i, child) in children.iter().enumerate() {
+                    if self.eval_logical(expr, child) {
+                        push(i);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn eval_query(&self, query: &Query, current: &'a JsonSize) -> Vec<Node<'a>> {
+        let start = if query.relative { current } else { self.root };
+        self.eval_segments(&query.segments, (vec![], start))
+    }
+
+    fn eval_logical(&self, expr: &LogicalExpr, current: &'a JsonSize) -> bool {
+        match expr {
+            LogicalExpr::Or(exprs) => exprs.iter().any(|x| self.eval_logical(x, current)),
+            LogicalExpr::And(exprs) => exprs.iter().all(|x| self.eval_logical(x, current)),
+            LogicalExpr::Not(expr) => !self.eval_logical(expr, current),
+            LogicalExpr::Test(query) => !self.eval_query(query, current).is_empty(),
+            LogicalExpr::Comparison(left, op, right) => {
+                let left = self.eval_comparable(left, current);
+                let right = self.eval_comparable(right, current);
+                match op {
+                    CmpOp::Eq => self.values_equal(&left, &right),
+                    CmpOp::Ne => !self.values_equal(&left, &right),
+                    CmpOp::Lt => values_less(&left, &right),
+                    CmpOp::Le => values_less(&left, &right) || self.values_equal(&left, &right),
+                    CmpOp::Gt => values_less(&right, &left),
+                    CmpOp::Ge => values_less(&right, &left) || self.values_equal(&left, &right),
+                }
+            }
+        }
+    }
+
+    fn eval_comparable(&self, comparable: &Comparable, current: &'a JsonSize) -> Value<'a> {
+        match comparable {
+            Comparable::Literal(literal) => Value::Literal(literal.clone()),
+            Comparable::Query(query) => match self.eval_query(query, current).pop() {
+                Some((_, node)) => match scalar_value(node, self.json) {
+                    Some(literal) => Value::Literal(literal),
+                    None => Value::Structured(node),
+                },
+                None => Value::Nothing,
+            },
+        }
+    }
+
+    fn values_equal(&self, a: &Value, b: &Value) -> bool {
+        match (a, b) {
+            (Value::Nothing, Value::Nothing) => true,
+            (Value::Literal(a), Value::Literal(b)) => a == b,
+            (Value::Structured(a), Value::Structured(b)) => self.nodes_equal(a, b),
+            _ => false,
+        }
+    }
+
+    fn nodes_equal(&self, a: &JsonSize, b: &JsonSize) -> bool {
+        let (ac, bc) = (a.children(), b.children());
+        match (a.value_kind(), b.value_kind()) {
+            (JsonValueKind::Array, JsonValueKind::Array) => {
+                ac.len() == bc.len() && ac.iter().zip(bc).all(|(a, b)| self.nodes_equal(a, b))
+            }
+            (JsonValueKind::Object, JsonValueKind::Object) => {
+                ac.len() == bc.len()
+                    && ac.iter().all(|a| {
+                        let name = unescape_json_string(a.key().get_key_str(self.json).unwrap());
+                        bc.iter().any(|b| {
+                            b.key().matches_token(self.json, &name) && self.nodes_equal(a, b)
+                        })
+                    })
+            }
+            (x, y) if x == y => scalar_value(a, self.json) == scalar_value(b, self.json),
+            _ => false,
+        }
+    }
+}
+
+/// Result of evaluating a comparable in a filter
+enum Value<'a> {
+    /// Singular query that selected no node
+    Nothing,
+    Literal(Literal),
+    /// Object or array
+    Structured(&'a JsonSize),
+}
+
+fn values_less(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Literal(Literal::Number(a)), Value::Literal(Literal::Number(b))) => a < b,
+        (Value::Literal(Literal::String(a)), Value::Literal(Literal::String(b))) => a < b,
+        _ => false,
+    }
+}
+
+/// Value of a scalar node, `None` for objects and arrays
+fn scalar_value(node: &JsonSize, json: &str) -> Option<Literal> {
+    let text = &json[node.span()];
+    match node.value_kind() {
+        JsonValueKind::Null => Some(Literal::Null),
+        JsonValueKind::Boolean => Some(Literal::Bool(text == "true")),
+        JsonValueKind::Number => text.parse().ok().map(Literal::Number),
+        JsonValueKind::String => Some(Literal::String(unescape_json_string(
+            &text[1..text.len() - 1],
+        ))),
+        JsonValueKind::Object | JsonValueKind::Array => None,
+    }
+}
+
+/// The node itself followed by all of its descendants, in document order
+fn descendants(node: Node) -> Vec<Node> {
+    let mut out = vec![];
+    let mut stack = vec![node];
+    while let Some((path, node)) = stack.pop() {
+        for (i, child) in node.children().iter().enumerate().rev() {
+            let mut child_path = path.clone();
+            child_path.push(i);
+            stack.push((child_path, child));
+        }
+        out.push((path, node));
+    }
+
+    out
+}
+
+fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    let len = i64::try_from(len).ok()?;
+    let i = if index < 0 { len + index } else { index };
+    if (0..len).contains(&i) {
+        usize::try_from(i).ok()
+    } else {
+        None
+    }
+}
+
+/// Array indices selected by a slice, following section 2.3.4.2.2 of RFC 9535
+fn slice_indices(
+    start: Option<i64>,
+    end: Option<i64>,
+    step: Option<i64>,
+    len: usize,
+) -> impl Iterator<Item = usize> {
+    let len = i64::try_from(len).unwrap();
+    let step = step.unwrap_or(1);
+    let normalize = |i: i64| if i >= 0 { i } else { len + i };
+    let (mut i, bound) = if step >= 0 {
+        let lower = normalize(start.unwrap_or(0)).clamp(0, len);
+        let upper = normalize(end.unwrap_or(len)).clamp(0, len);
+        (lower, upper)
+    } else {
+        let upper = normalize(start.unwrap_or(len - 1)).clamp(-1, len - 1);
+        let lower = normalize(end.unwrap_or(-len - 1)).clamp(-1, len - 1);
+        (upper, lower)
+    };
+
+    std::iter::from_fn(move || {
+        let in_range = match step {
+            0 => false,
+            1.. => i < bound,
+            _ => bound < i,
+        };
+        if !in_range {
+            return None;
+        }
+        let x = i;
+        i += step;
+        Some(x as usize)
+    })
+}
+
+/// Format the matched nodes like `display_list`, followed by the sum of their sizes. Matches
+/// inside another match, and repeated matches, are listed but only counted once in the sum.
+pub fn display_matches(
+    root: &JsonSize,
+    json: &str,
+    paths: &[Vec<usize>],
+    options: &DisplayOptions,
+) -> Vec<String> {
+    let file_total = root.total_size();
+    let matched: HashSet<&[usize]> = paths.iter().map(|path| path.as_slice()).collect();
+    let mut counted = HashSet::new();
+    let mut lines = vec![];
+    let mut sum = 0;
+    let mut nested = 0;
+    for path in paths {
+        let js = root.get_path(path).unwrap();
+        let size = js.size(options.accounting);
+        let inside_match = (0..path.len()).any(|i| matched.contains(&path[..i]));
+        if inside_match || !counted.insert(path.as_slice()) {
+            nested += 1;
+        } else {
+            sum += size;
+        }
+        let mut compressed = String::new();
+        if !options.compressors.is_empty() {
+            let minified = minify_dialect(&json[js.span()], options.dialect, false);
+            for compressor in options.compressors.iter() {
+                compressed.push_str(&compressor.to_display(minified.as_bytes()));
+                compressed.push(' ');
+            }
+        }
+        lines.push(format!(
+            "{:12} {} {}{}",
+            size,
+            size_bar(size, file_total),
+            compressed,
+            root.path_to_json_pointer(json, path).unwrap()
+        ));
+    }
+    let nested = if nested > 0 {
+        format!(" ({} nested or repeated not counted)", nested)
+    } else {
+        String::new()
+    };
+    lines.push(format!(
+        "{:12} {} Total: {} matches{}, {:.1}% of {} bytes",
+        sum,
+        size_bar(sum, file_total),
+        paths.len(),
+        nested,
+        percent_of(sum, file_total),
+        file_total
+    ));
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(json: &str, path: &str) -> Vec<String> {
+        let js = JsonSize::new(json);
+        let path: JsonPath = path.parse().unwrap();
+        path.evaluate(&js, json)
+            .iter()
+            .map(|path| js.path_to_json_pointer(json, path).unwrap())
+            .collect()
+    }
+
+    const STORE: &str = r#"{ "store": {
+        "book": [
+          { "category": "reference", "author": "Nigel Rees", "title": "Sayings of the Century", "price": 8.95 },
+          { "category": "fiction", "author": "Evelyn Waugh", "title": "Sword of Honour", "price": 12.99 },
+          { "category": "fiction", "author": "Herman Melville", "title": "Moby Dick", "isbn": "0-553-21311-3", "price": 8.99 },
+          { "category": "fiction", "author": "J. R. R. Tolkien", "title": "The Lord of the Rings", "isbn": "0-395-19395-8", "price": 22.99 }
+        ],
+        "bicycle": { "color": "red", "price": 399 }
+    } }"#;
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("".parse::<JsonPath>().is_err());
+        assert!("$.".parse::<JsonPath>().is_err());
+        assert!("$[01]".parse::<JsonPath>().is_err());
+        assert!("$[?@.a == $..b]".parse::<JsonPath>().is_err());
+        assert!("$[?length(@) > 1]".parse::<JsonPath>().is_err());
+        assert!("$['a'".parse::<JsonPath>().is_err());
+        assert!("$[?1]".parse::<JsonPath>().is_err());
+    }
+
+    #[test]
+    fn test_child_and_descendant_segments() {
+        assert_eq!(
+            query(STORE, "$.store.book[*].author"),
+            vec![
+                "/store/book/0/author",
+                "/store/book/1/author",
+                "/store/book/2/author",
+                "/store/book/3/author"
+            ]
+        );
+        assert_eq!(query(STORE, "$..author").len(), 4);
+        assert_eq!(query(STORE, "$.store.*").len(), 2);
+        assert_eq!(query(STORE, "$.store..price").len(), 5);
+        assert_eq!(query(STORE, "$..book[2]"), vec!["/store/book/2"]);
+        assert_eq!(query(STORE, "$..book[-1]"), vec!["/store/book/3"]);
+        assert_eq!(
+            query(STORE, "$['store'][\"bicycle\"]"),
+            vec!["/store/bicycle"]
+        );
+        assert_eq!(query(STORE, "$.nothing"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_slices() {
+        let json = r#"["a", "b", "c", "d", "e", "f", "g"]"#;
+        assert_eq!(query(json, "$[1:3]"), vec!["/1", "/2"]);
+        assert_eq!(query(json, "$[5:]"), vec!["/5", "/6"]);
+        assert_eq!(query(json, "$[1:5:2]"), vec!["/1", "/3"]);
+        assert_eq!(query(json, "$[5:1:-2]"), vec!["/5", "/3"]);
+        assert_eq!(
+            query(json, "$[::-1]"),
+            vec!["/6", "/5", "/4", "/3", "/2", "/1", "/0"]
+        );
+        assert_eq!(query(json, "$[::0]"), Vec::<String>::new());
+        assert_eq!(query(json, "$[0, 0, -1]"), vec!["/0", "/0", "/6"]);
+    }
+
+    #[test]
+    fn test_filters() {
+        assert_eq!(
+            query(STORE, "$..book[?@.isbn]"),
+            vec!["/store/book/2", "/store/book/3"]
+        );
+        assert_eq!(
+            query(STORE, "$..book[?@.price < 10].title"),
+            vec!["/store/book/0/title", "/store/book/2/title"]
+        );
+        assert_eq!(
+            query(
+                STORE,
+                "$..book[?@.category == 'fiction' && !(@.price > 20)].title"
+            ),
+            vec!["/store/book/1/title", "/store/book/2/title"]
+        );
+        assert_eq!(
+            query(STORE, "$..book[?@.price > $.store.bicycle.price]"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            query(STORE, "$..*[?@ == 'red']"),
+            vec!["/store/bicycle/color"]
+        );
+        let json = r#"[{"a": [1, {"b": 2}]}, {"a": [1, {"b": 3}]}, {"a": null}]"#;
+        assert_eq!(query(json, "$[?@.a == $[0].a]"), vec!["/0"]);
+        assert_eq!(query(json, "$[?@.x == @.y]"), vec!["/0", "/1", "/2"]);
+        assert_eq!(
+            query(json, "$[?@.a == null || @.a[1].b >= 3]"),
+            vec!["/1", "/2"]
+        );
+    }
+
+    #[test]
+    fn test_display_matches_overlapping() {
+        let json = r#"{"a": [1, 2], "b": 3}"#;
+        let js = JsonSize::new(json);
+        let options = DisplayOptions::default();
+        let total = |path: &str| {
+            let paths = path.parse::<JsonPath>().unwrap().evaluate(&js, json);
+            let lines = display_matches(&js, json, &paths, &options);
+            lines.last().unwrap().trim_start().to_string()
+        };
+        // /a/0 and /a/1 are inside /a, the sum is the whole object without brackets
+        assert_eq!(
+            total("$..*"),
+            "19 [##########] Total: 4 matches (2 nested or repeated not counted), 90.5% of 21 bytes"
+        );
+        // ", \"b\": 3" is counted once
+        assert_eq!(
+            total("$.b"),
+            "7 [####      ] Total: 1 matches, 33.3% of 21 bytes"
+        );
+        assert_eq!(
+            total("$['b', 'b']"),
+            "7 [####      ] Total: 2 matches (1 nested or repeated not counted), 33.3% of 21 bytes"
+        );
+    }
+}
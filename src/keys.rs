@@ -0,0 +1,176 @@
+//! Report the bytes spent on object keys, grouped by key name.
+//! Long keys repeated in every record of an array can take a big part of the file.
+use crate::size::{canonical_json_string, percent_of, size_bar, JsonSize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KeyNameStats {
+    /// Number of object members with this key
+    pub count: usize,
+    /// Bytes of the keys, including quotes, colon and the whitespace around them
+    pub key_bytes: usize,
+    /// Bytes of the values of those members
+    pub value_bytes: usize,
+}
+
+/// Accumulates `KeyNameStats` for every distinct key name, can be fed more than one tree
+#[derive(Debug, Default)]
+pub struct KeyStats {
+    /// Indexed by the key without quotes and with only the required escape sequences, so
+    /// `"\u0061"` and `"a"` are the same key
+    keys: HashMap<String, KeyNameStats>,
+    /// Sum of the sizes of all the trees
+    total: usize,
+}
+
+impl KeyStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, js: &JsonSize, json: &str) {
+        self.total += js.total_size();
+        self.add_children(js, json);
+    }
+
+    fn add_children(&mut self, js: &JsonSize, json: &str) {
+        for child in js.children() {
+            if let Some(key) = canonical_key(child, json) {
+                let key = &key[1..key.len() - 1];
+                let stats = match self.keys.get_mut(key) {
+                    Some(stats) => stats,
+                    None => self.keys.entry(key.to_string()).or_default(),
+                };
+                stats.count += 1;
+                stats.key_bytes += child.key_size();
+                stats.value_bytes += child.total_size();
+            }
+            self.add_children(child, json);
+        }
+    }
+
+    /// All the key names, most bytes spent on keys first
+    pub fn sorted(&self) -> Vec<(&str, &KeyNameStats)> {
+        let mut keys: Vec<_> = self
+            .keys
+            .iter()
+            .map(|(key, stats)| (key.as_str(), stats))
+            .collect();
+        keys.sort_by(|a, b| b.1.key_bytes.cmp(&a.1.key_bytes).then_with(|| a.0.cmp(b.0)));
+        keys
+    }
+
+    /// Format as a table, showing at most `limit` key names, followed by the sum of all keys
+    pub fn display_list(&self, limit: Option<usize>) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{:>12} {:12} {:>10} {:>12} Key",
+            "Key bytes", "", "Count", "Value bytes"
+        )];
+        let sorted = self.sorted();
+        for (key, stats) in sorted.iter().take(limit.unwrap_or(usize::MAX)) {
+            lines.push(format!(
+                "{:12} {} {:10} {:12} \"{}\"",
+                stats.key_bytes,
+                size_bar(stats.key_bytes, self.total),
+                stats.count,
+                stats.value_bytes,
+                key
+            ));
+        }
+
+        let key_bytes: usize = sorted.iter().map(|(_, stats)| stats.key_bytes).sum();
+        let count: usize = sorted.iter().map(|(_, stats)| stats.count).sum();
+        lines.push(format!(
+            "{:12} {} {:10} {:12} Total: {} distinct keys, {:.1}% of {} bytes",
+            key_bytes,
+            size_bar(key_bytes, self.total),
+            count,
+            "",
+            sorted.len(),
+            percent_of(key_bytes, self.total),
+            self.total
+        ));
+
+        lines
+    }
+}
+
+/// The key of `js` like `canonical_json_string`, with quotes
+fn canonical_key<'a>(js: &JsonSize, json: &'a str) -> Option<Cow<'a, str>> {
+    let raw = &json[js.key_text_span()?];
+    if raw.starts_with(['"', '\'']) {
+        Some(canonical_json_string(raw))
+    } else {
+        // Unquoted JSON5 identifier
+        let quoted = format!("\"{}\"", raw);
+        Some(Cow::Owned(canonical_json_string(&quoted).into_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::size::Dialect;
+
+    #[test]
+    fn test_key_stats() {
+        let json = r#"[{"customerId": 1, "n": {"n": null}}, {"customerId": 23}]"#;
+        let js = JsonSize::new(json);
+        let mut keys = KeyStats::new();
+        keys.add(&js, json);
+
+        let sorted = keys.sorted();
+        assert_eq!(sorted.len(), 2);
+        assert_eq!(
+            sorted[0],
+            (
+                "customerId",
+                &KeyNameStats {
+                    count: 2,
+                    key_bytes: 2 * 13,
+                    value_bytes: 2 + 3,
+                }
+            )
+        );
+        assert_eq!(
+            sorted[1],
+            (
+                "n",
+                &KeyNameStats {
+                    count: 2,
+                    // The space before the second one
+                    key_bytes: 2 * 4 + 1,
+                    value_bytes: 12 + 5,
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_key_stats_json5() {
+        let json = "{a: 1, 'b' : 2}";
+        let js = JsonSize::with_dialect(json, Dialect::Json5);
+        let mut keys = KeyStats::new();
+        keys.add(&js, json);
+
+        let sorted = keys.sorted();
+        assert_eq!(sorted[0].0, "b");
+        assert_eq!(sorted[0].1.key_bytes, " 'b' :".len());
+        assert_eq!(sorted[1].0, "a");
+        assert_eq!(sorted[1].1.key_bytes, "a:".len());
+    }
+
+    #[test]
+    fn test_escapes_are_decoded() {
+        let json = r#"[{"a": 1}, {"\u0061": 2}, {"\"": 3}]"#;
+        let mut keys = KeyStats::new();
+        keys.add(&JsonSize::new(json), json);
+
+        let sorted = keys.sorted();
+        assert_eq!(sorted.len(), 2);
+        assert_eq!(sorted[0].0, "a");
+        assert_eq!(sorted[0].1.count, 2);
+        assert_eq!(sorted[1].0, "\\\"");
+    }
+}
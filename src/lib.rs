@@ -1,3 +1,25 @@
+pub mod archive;
+pub mod blobs;
+pub mod compress;
+pub mod decompress;
+pub mod distribution;
+pub mod duplicates;
+pub mod encoding;
+pub mod entropy;
+pub mod files;
+pub mod follow;
+pub mod jsonpath;
+pub mod keys;
+pub mod memory;
 pub mod minify;
+pub mod ndjson;
+pub mod numbers;
+pub mod payload;
+pub mod pointer;
 pub mod prettify;
+pub mod schema;
 pub mod size;
+pub mod stats;
+pub mod strings;
+pub mod top;
+pub mod yaml;
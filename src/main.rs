@@ -1,7 +1,20 @@
 use clap::Parser;
+use jsdu::archive::ArchiveFormat;
+use jsdu::compress::Compressor;
+use jsdu::decompress::Compression;
+use jsdu::encoding::{Decoded, Encoding};
+use jsdu::follow::{FollowStats, Tail};
+use jsdu::jsonpath::JsonPath;
+use jsdu::ndjson::{Aggregate, RecordReader, RecordStats};
+use jsdu::numbers::Precision;
+use jsdu::pointer::{Position, RelativeJsonPointer, RelativeTarget};
+use jsdu::size::{Accounting, Dialect, DisplayOptions, SortBy};
+use std::collections::HashSet;
 use std::fs;
+use std::io::{self, IsTerminal, Read};
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy, clap::ValueEnum)]
 pub enum ByteFormat {
@@ -22,6 +35,24 @@ fn dft_format() -> ByteFormat {
     }
 }
 
+/// Seconds between refreshes of --follow. Too short intervals would keep a core busy.
+fn parse_interval(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(x) if (0.1..=1e9).contains(&x) => Ok(x),
+        Ok(_) => Err("expected between 0.1 and 1e9 seconds".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, clap::ValueEnum)]
+pub enum NdjsonView {
+    Records,
+    Schema,
+    Keys,
+    Strings,
+    Payloads,
+}
+
 /// JSON file size analyzer
 #[derive(Debug, Parser)]
 #[clap(name = "jsdu", version)]
@@ -59,13 +90,260 @@ pub enum Command {
         /// JSON pointer to navigate to before printing size (RFC 6901)
         #[clap(long = "pointer")]
         pointer: Option<String>,
+        /// Relative JSON pointer, evaluated starting from --pointer
+        #[clap(long = "relative-pointer")]
+        relative_pointer: Option<RelativeJsonPointer>,
+        /// JSONPath query (RFC 9535), print the size of each match and their sum
+        #[clap(
+            long = "path",
+            conflicts_with_all = ["pointer", "relative_pointer", "breakdown", "entropy", "memory", "sort"]
+        )]
+        path: Option<JsonPath>,
+        /// "member" counts the key, colon and comma of each item, the bytes saved by deleting it.
+        /// "value" only counts the value.
+        #[clap(long = "accounting", default_value = "member")]
+        accounting: Accounting,
+        /// Add columns with the whitespace, control characters and data of each item
+        #[clap(long = "breakdown")]
+        breakdown: bool,
+        /// Add columns with the size of each item after minifying and compressing it, and print
+        /// the compressed size of the whole file. Format: "gzip", "zstd" or with a level
+        /// "gzip:9", can be repeated.
+        #[clap(long = "compress", value_delimiter = ',')]
+        compress: Vec<Compressor>,
+        /// Add a column with the entropy of the data of each item, in bits per byte, and flag
+        /// items that look random (hashes, base64, encrypted data) or very redundant
+        #[clap(long = "entropy")]
+        entropy: bool,
+        /// Add a column with the estimated memory used by each item once loaded into a DOM like
+        /// serde_json's Value
+        #[clap(long = "memory")]
+        memory: bool,
+        /// Order of the items: "none" (as in the file), "size" or "entropy"
+        #[clap(long = "sort", default_value = "none")]
+        sort: SortBy,
+        /// Treat strings that contain JSON, or base64 encoded JSON, as objects and arrays. The
+        /// JSON pointer can continue inside them, and if it points to one it is expanded.
+        #[clap(long = "expand", conflicts_with = "path")]
+        expand: bool,
+        /// Treat the input as newline delimited JSON and keep reading records appended to it,
+        /// printing the bytes per second of each path and the largest recent records. Handles
+        /// truncated and rotated files.
+        #[clap(long = "follow", conflicts_with_all = ["pointer", "relative_pointer", "path", "expand"])]
+        follow: bool,
+        /// With --follow, seconds between refreshes, at least 0.1
+        #[clap(long = "interval", default_value_t = 2.0, requires = "follow", value_parser = parse_interval)]
+        interval: f64,
+        /// With --follow, read the records already in the file instead of starting at the end
+        #[clap(long = "from-start", requires = "follow")]
+        from_start: bool,
+        /// "json", "jsonc" (comments and trailing commas) or "json5". By default "jsonc" for
+        /// .jsonc files, "json5" for .json5 files, and "json" otherwise.
+        #[clap(long = "dialect", conflicts_with = "follow")]
+        dialect: Option<Dialect>,
+        /// Report the sizes of UTF-16 and UTF-32 files as if they were UTF-8, without byte order
+        /// mark. By default sizes are in bytes of the original encoding.
+        #[clap(long = "utf8-sizes")]
+        utf8_sizes: bool,
+        /// With several inputs, a directory or an archive, number of biggest members listed below
+        /// each file
+        #[clap(long = "fields", default_value_t = 3)]
+        fields: usize,
+        /// Input JSON file. With several files or directories, print a combined tree of the
+        /// directories, the files and the biggest members of each file.
+        #[clap(value_parser, required = true)]
+        input: Vec<PathBuf>,
+    },
+    /// Aggregate sizes over paths where array indices are replaced by `*`
+    #[clap(name = "schema")]
+    Schema {
+        /// JSON pointer to navigate to before aggregating (RFC 6901)
+        #[clap(long = "pointer")]
+        pointer: Option<String>,
+        /// Only show the N biggest paths
+        #[clap(short = 'n', long = "limit")]
+        limit: Option<usize>,
+        /// "member" counts the key, colon and comma of each item, the bytes saved by deleting it.
+        /// "value" only counts the value.
+        #[clap(long = "accounting", default_value = "member")]
+        accounting: Accounting,
+        /// Input JSON file
+        #[clap(value_parser)]
+        input: PathBuf,
+    },
+    /// Report the bytes spent on object keys, grouped by key name
+    #[clap(name = "keys")]
+    Keys {
+        /// JSON pointer to navigate to before aggregating (RFC 6901)
+        #[clap(long = "pointer")]
+        pointer: Option<String>,
+        /// Only show the N key names with the most bytes
+        #[clap(short = 'n', long = "limit")]
+        limit: Option<usize>,
+        /// Input JSON file
+        #[clap(value_parser)]
+        input: PathBuf,
+    },
+    /// Print the number of values and bytes of each kind, and other statistics
+    #[clap(name = "stats")]
+    Stats {
+        /// JSON pointer to navigate to before printing statistics (RFC 6901)
+        #[clap(long = "pointer")]
+        pointer: Option<String>,
+        /// Input JSON file
+        #[clap(value_parser)]
+        input: PathBuf,
+    },
+    /// Print the largest values anywhere in the file
+    #[clap(name = "top")]
+    Top {
+        /// How many values to print
+        #[clap(short = 'n', default_value_t = 20)]
+        n: usize,
+        /// Also print the largest objects and arrays that do not contain each other
+        #[clap(long = "containers")]
+        containers: bool,
+        /// JSON pointer to navigate to before searching (RFC 6901)
+        #[clap(long = "pointer")]
+        pointer: Option<String>,
+        /// Input JSON file
+        #[clap(value_parser)]
+        input: PathBuf,
+    },
+    /// Print the distribution of item sizes of each array, and the items that are much bigger
+    /// than the median
+    #[clap(name = "arrays")]
+    Arrays {
+        /// Ignore arrays with less items than this
+        #[clap(long = "min-len", default_value_t = 10)]
+        min_len: usize,
+        /// Items bigger than this many times the median are outliers
+        #[clap(long = "outlier-factor", default_value_t = 10.0)]
+        outlier_factor: f64,
+        /// Only print the N biggest arrays
+        #[clap(short = 'n', long = "limit")]
+        limit: Option<usize>,
+        /// JSON pointer to navigate to before searching (RFC 6901)
+        #[clap(long = "pointer")]
+        pointer: Option<String>,
+        /// Input JSON file
+        #[clap(value_parser)]
+        input: PathBuf,
+    },
+    /// Find values that appear more than once and how many bytes removing them would save
+    #[clap(name = "duplicates", visible_alias = "dup")]
+    Duplicates {
+        /// Ignore values smaller than this, in bytes after minifying
+        #[clap(long = "min-size", default_value_t = 64)]
+        min_size: usize,
+        /// Only print the N duplicates with the most savings
+        #[clap(short = 'n', long = "limit")]
+        limit: Option<usize>,
+        /// JSON pointer to navigate to before searching (RFC 6901)
+        #[clap(long = "pointer")]
+        pointer: Option<String>,
+        /// Input JSON file
+        #[clap(value_parser)]
+        input: PathBuf,
+    },
+    /// Print the most repeated string values, and the bytes saved by replacing the strings of
+    /// each path with codes into a dictionary
+    #[clap(name = "strings")]
+    Strings {
+        /// JSON pointer to navigate to before aggregating (RFC 6901)
+        #[clap(long = "pointer")]
+        pointer: Option<String>,
+        /// Only show the first N values and paths
+        #[clap(short = 'n', long = "limit")]
+        limit: Option<usize>,
         /// Input JSON file
         #[clap(value_parser)]
         input: PathBuf,
     },
+    /// Classify strings: base64, data URIs, hex, UUIDs, timestamps, URLs and stringified JSON
+    #[clap(name = "payloads")]
+    Payloads {
+        /// JSON pointer to navigate to before classifying (RFC 6901)
+        #[clap(long = "pointer")]
+        pointer: Option<String>,
+        /// Only show the N paths with the most bytes
+        #[clap(short = 'n', long = "limit")]
+        limit: Option<usize>,
+        /// Input JSON file
+        #[clap(value_parser)]
+        input: PathBuf,
+    },
+    /// Report the precision of numbers by path, and the bytes saved by rounding them
+    #[clap(name = "numbers")]
+    Numbers {
+        /// Count numbers with more than N digits after the decimal point (default 6)
+        #[clap(long = "decimals", conflicts_with = "significant")]
+        decimals: Option<u32>,
+        /// Count numbers with more than N significant digits, at least 1
+        #[clap(long = "significant", value_parser = clap::value_parser!(u32).range(1..))]
+        significant: Option<u32>,
+        /// JSON pointer to navigate to before aggregating (RFC 6901)
+        #[clap(long = "pointer")]
+        pointer: Option<String>,
+        /// Only show the N paths with the most savings
+        #[clap(short = 'n', long = "limit")]
+        limit: Option<usize>,
+        /// Input JSON file
+        #[clap(value_parser)]
+        input: PathBuf,
+    },
+    /// Read newline delimited JSON, or concatenated JSON values, one record at a time
+    #[clap(name = "ndjson", visible_alias = "jsonl")]
+    Ndjson {
+        /// "records" lists the largest records, "schema", "keys", "strings" and "payloads"
+        /// aggregate all the records like the commands with the same name
+        #[clap(long = "view", value_enum, default_value_t = NdjsonView::Records)]
+        view: NdjsonView,
+        /// Only show the first N rows
+        #[clap(short = 'n', long = "limit", default_value_t = 20)]
+        limit: usize,
+        /// Accounting used by the schema view
+        #[clap(long = "accounting", default_value = "member")]
+        accounting: Accounting,
+        /// Values can span many lines, like concatenated pretty printed JSON. By default every
+        /// line is a record, and a line with unclosed brackets is an invalid record.
+        #[clap(long = "concatenated")]
+        concatenated: bool,
+        /// Input file, or "-" for stdin
+        #[clap(value_parser)]
+        input: PathBuf,
+    },
+    /// Print the JSON pointer of the value at a given position of the file
+    #[clap(name = "locate")]
+    Locate {
+        /// Input JSON file
+        #[clap(value_parser)]
+        input: PathBuf,
+        /// Byte offset starting at 0, or line:column starting at 1:1
+        #[clap(value_parser)]
+        position: Position,
+    },
     /// Minify file and exit. Will remove all whitespace.
     #[clap(name = "minify", visible_alias = "min")]
     Minify {
+        /// "json", "jsonc" (comments and trailing commas) or "json5". By default "jsonc" for
+        /// .jsonc files, "json5" for .json5 files, and "json" otherwise.
+        #[clap(long = "dialect")]
+        dialect: Option<Dialect>,
+        /// Keep comments instead of removing them
+        #[clap(long = "keep-comments")]
+        keep_comments: bool,
+        /// Convert the output to "utf-8", "utf-16le", "utf-16be", "utf-32le" or "utf-32be". By
+        /// default the encoding of the input is kept.
+        #[clap(long = "encoding")]
+        encoding: Option<Encoding>,
+        /// Remove the byte order mark instead of keeping it
+        #[clap(long = "strip-bom")]
+        strip_bom: bool,
+        /// Write the output uncompressed. By default compressed input files are compressed again
+        /// with the same format.
+        #[clap(long = "decompress")]
+        decompress: bool,
         /// Input JSON file
         #[clap(value_parser)]
         input: PathBuf,
@@ -76,6 +354,60 @@ pub enum Command {
         /// How many spaces
         #[clap(long = "indent", default_value_t = 4)]
         indent: u32,
+        /// "json", "jsonc" (comments and trailing commas) or "json5". By default "jsonc" for
+        /// .jsonc files, "json5" for .json5 files, and "json" otherwise. Comments are kept.
+        #[clap(long = "dialect")]
+        dialect: Option<Dialect>,
+        /// Convert the output to "utf-8", "utf-16le", "utf-16be", "utf-32le" or "utf-32be". By
+        /// default the encoding of the input is kept.
+        #[clap(long = "encoding")]
+        encoding: Option<Encoding>,
+        /// Remove the byte order mark instead of keeping it
+        #[clap(long = "strip-bom")]
+        strip_bom: bool,
+        /// Write the output uncompressed. By default compressed input files are compressed again
+        /// with the same format.
+        #[clap(long = "decompress")]
+        decompress: bool,
+        /// Input JSON file
+        #[clap(value_parser)]
+        input: PathBuf,
+    },
+    /// Round numbers in place, the rest of the file is not modified
+    #[clap(name = "round")]
+    Round {
+        /// Keep N digits after the decimal point
+        #[clap(
+            long = "decimals",
+            required_unless_present = "significant",
+            conflicts_with = "significant"
+        )]
+        decimals: Option<u32>,
+        /// Keep N significant digits, at least 1
+        #[clap(long = "significant", value_parser = clap::value_parser!(u32).range(1..))]
+        significant: Option<u32>,
+        /// JSONPath query (RFC 9535), only round the numbers inside the matches
+        #[clap(long = "path")]
+        path: Option<JsonPath>,
+        /// Input JSON file
+        #[clap(value_parser)]
+        input: PathBuf,
+    },
+    /// Decode base64 strings and data URIs and save them to files named after their JSON pointer
+    #[clap(name = "extract-blobs")]
+    ExtractBlobs {
+        /// Output directory, created if it does not exist
+        #[clap(long = "out")]
+        out: PathBuf,
+        /// Ignore strings smaller than this, in bytes after decoding
+        #[clap(long = "min-size", default_value_t = 1024)]
+        min_size: usize,
+        /// Replace each blob in the input file with the path of the file it was saved to
+        #[clap(long = "rewrite")]
+        rewrite: bool,
+        /// JSON pointer to navigate to before searching (RFC 6901)
+        #[clap(long = "pointer")]
+        pointer: Option<String>,
         /// Input JSON file
         #[clap(value_parser)]
         input: PathBuf,
@@ -100,44 +432,952 @@ fn main() {
         Command::Interactive { input: _ } => {
             unimplemented!("interactive mode not ready :(")
         }
-        Command::Show { input, pointer } => {
-            show(&input, pointer.as_deref());
+        Command::Show {
+            input,
+            pointer,
+            relative_pointer,
+            path,
+            accounting,
+            breakdown,
+            compress,
+            entropy,
+            memory,
+            sort,
+            expand,
+            follow,
+            interval,
+            from_start,
+            dialect,
+            utf8_sizes,
+            fields,
+        } => {
+            // Directories and archives are listed file by file, without these
+            let single_file_flags: Vec<_> = [
+                ("--pointer", pointer.is_some()),
+                ("--relative-pointer", relative_pointer.is_some()),
+                ("--path", path.is_some()),
+                ("--accounting value", accounting != Accounting::Member),
+                ("--breakdown", breakdown),
+                ("--compress", !compress.is_empty()),
+                ("--entropy", entropy),
+                ("--memory", memory),
+                ("--sort entropy", sort == SortBy::Entropy),
+                ("--expand", expand),
+                ("--follow", follow),
+                ("--dialect", dialect.is_some()),
+                ("--utf8-sizes", utf8_sizes),
+            ]
+            .into_iter()
+            .filter_map(|(flag, used)| used.then_some(flag))
+            .collect();
+            if input.len() > 1 || input[0].is_dir() {
+                if !single_file_flags.is_empty() {
+                    eprintln!("{} need a single input file", single_file_flags.join(", "));
+                    std::process::exit(1);
+                }
+                show_directories(&input, fields, sort == SortBy::Size);
+            } else if archive_format(&input[0]).is_some() {
+                if !single_file_flags.is_empty() {
+                    eprintln!(
+                        "{} can not be used with an archive, read one of its files with {}!/path/inside/archive.json",
+                        single_file_flags.join(", "),
+                        input[0].display()
+                    );
+                    std::process::exit(1);
+                }
+                show_archive(&input[0], fields, sort == SortBy::Size);
+            } else {
+                let input = input.into_iter().next().unwrap();
+                let options = DisplayOptions {
+                    accounting,
+                    breakdown,
+                    compressors: compress,
+                    entropy,
+                    memory,
+                    sort,
+                    dialect: input_dialect(&input, dialect),
+                };
+                if follow {
+                    show_follow(&input, accounting, interval, from_start);
+                } else if let Some(path) = path {
+                    show_json_path(&input, &path, utf8_sizes, &options);
+                } else {
+                    show(
+                        &input,
+                        pointer.as_deref(),
+                        relative_pointer.as_ref(),
+                        expand,
+                        utf8_sizes,
+                        &options,
+                    );
+                }
+            }
+        }
+        Command::Schema {
+            input,
+            pointer,
+            limit,
+            accounting,
+        } => {
+            schema(&input, pointer.as_deref(), limit, accounting);
+        }
+        Command::Keys {
+            input,
+            pointer,
+            limit,
+        } => {
+            keys(&input, pointer.as_deref(), limit);
+        }
+        Command::Stats { input, pointer } => {
+            stats(&input, pointer.as_deref());
+        }
+        Command::Top {
+            input,
+            n,
+            containers,
+            pointer,
+        } => {
+            top(&input, pointer.as_deref(), n, containers);
+        }
+        Command::Arrays {
+            input,
+            min_len,
+            outlier_factor,
+            limit,
+            pointer,
+        } => {
+            arrays(&input, pointer.as_deref(), min_len, outlier_factor, limit);
+        }
+        Command::Duplicates {
+            input,
+            min_size,
+            limit,
+            pointer,
+        } => {
+            duplicates(&input, pointer.as_deref(), min_size, limit);
+        }
+        Command::Strings {
+            input,
+            pointer,
+            limit,
+        } => {
+            strings(&input, pointer.as_deref(), limit);
+        }
+        Command::Payloads {
+            input,
+            pointer,
+            limit,
+        } => {
+            payloads(&input, pointer.as_deref(), limit);
+        }
+        Command::Numbers {
+            input,
+            decimals,
+            significant,
+            pointer,
+            limit,
+        } => {
+            let precision = match significant {
+                Some(n) => Precision::Significant(n),
+                None => Precision::Decimals(decimals.unwrap_or(6)),
+            };
+            numbers(&input, pointer.as_deref(), limit, precision);
         }
-        Command::Minify { input } => {
-            let json = fs::read_to_string(&input).unwrap();
-            let size_before = json.len();
-            let minified = jsdu::minify::minify(&json);
-            let size_after = minified.len();
-            fs::write(&input, minified).unwrap();
-            println!("minified from {} to {} bytes", size_before, size_after);
+        Command::Ndjson {
+            input,
+            view,
+            limit,
+            accounting,
+            concatenated,
+        } => {
+            ndjson(&input, view, limit, accounting, concatenated);
         }
-        Command::Prettify { input, indent } => {
-            let json = fs::read_to_string(&input).unwrap();
-            let size_before = json.len();
-            let prettified = jsdu::prettify::prettify(&json, usize::try_from(indent).unwrap());
-            let size_after = prettified.len();
-            fs::write(&input, prettified).unwrap();
-            println!("prettified from {} to {} bytes", size_before, size_after);
+        Command::Locate { input, position } => {
+            locate(&input, position);
+        }
+        Command::Minify {
+            input,
+            dialect,
+            keep_comments,
+            encoding,
+            strip_bom,
+            decompress,
+        } => {
+            exit_if_yaml(&input, "minified");
+            let input_file = read_input(&input);
+            let dialect = input_dialect(&input, dialect);
+            let minified =
+                jsdu::minify::minify_dialect(&input_file.decoded.text, dialect, keep_comments);
+            write_output(
+                &input,
+                &input_file,
+                minified,
+                "minified",
+                encoding,
+                strip_bom,
+                decompress,
+            );
+        }
+        Command::Prettify {
+            input,
+            indent,
+            dialect,
+            encoding,
+            strip_bom,
+            decompress,
+        } => {
+            exit_if_yaml(&input, "prettified");
+            let input_file = read_input(&input);
+            let dialect = input_dialect(&input, dialect);
+            let prettified = jsdu::prettify::prettify_dialect(
+                &input_file.decoded.text,
+                usize::try_from(indent).unwrap(),
+                dialect,
+            );
+            write_output(
+                &input,
+                &input_file,
+                prettified,
+                "prettified",
+                encoding,
+                strip_bom,
+                decompress,
+            );
+        }
+        Command::Round {
+            input,
+            decimals,
+            significant,
+            path,
+        } => {
+            exit_if_yaml(&input, "rounded");
+            let input_file = read_input(&input);
+            let json = &input_file.decoded.text;
+            let precision = match significant {
+                Some(n) => Precision::Significant(n),
+                None => Precision::Decimals(decimals.unwrap()),
+            };
+            let js = parse_json(&input, &input_file.decoded, None);
+            let nodes: Vec<_> = match path {
+                Some(path) => path
+                    .evaluate(&js, json)
+                    .iter()
+                    .map(|path| js.get_path(path).unwrap())
+                    .collect(),
+                None => vec![&js],
+            };
+            let rounded = jsdu::numbers::round_numbers(json, &nodes, precision);
+            write_output(&input, &input_file, rounded, "rounded", None, false, false);
+        }
+        Command::ExtractBlobs {
+            input,
+            out,
+            min_size,
+            rewrite,
+            pointer,
+        } => {
+            extract_blobs(&input, pointer.as_deref(), &out, min_size, rewrite);
         }
     }
 }
 
-fn show(path: &Path, json_pointer: Option<&str>) {
-    let json = match fs::read_to_string(path) {
-        Ok(contents) => contents,
+/// Read a JSON file in any dialect and encoding, or a YAML file converted to JSON with the
+/// sizes of the YAML text
+fn read_json_size(path: &Path, dialect: Option<Dialect>) -> (String, jsdu::size::JsonSize) {
+    let input = read_input(path).decoded;
+    if jsdu::yaml::is_yaml_path(path) {
+        let yaml = parse_yaml(path, &input);
+        (yaml.json, yaml.js)
+    } else {
+        let js = parse_json(path, &input, dialect);
+        (input.text, js)
+    }
+}
+
+/// Parse a decoded JSON file, with the sizes in bytes of its original encoding
+fn parse_json(path: &Path, decoded: &Decoded, dialect: Option<Dialect>) -> jsdu::size::JsonSize {
+    jsdu::files::parse_decoded(decoded, input_dialect(path, dialect)).unwrap_or_else(|e| {
+        eprintln!("Error parsing {}: {}", path.display(), e);
+        std::process::exit(1);
+    })
+}
+
+/// Commands that rewrite the file only write JSON
+fn exit_if_yaml(path: &Path, action: &str) {
+    if jsdu::yaml::is_yaml_path(path) {
+        eprintln!(
+            "{} is YAML, only JSON files can be {}",
+            path.display(),
+            action
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Parse a decoded YAML file, with the sizes in bytes of its original encoding
+fn parse_yaml(path: &Path, decoded: &Decoded) -> jsdu::yaml::YamlFile {
+    jsdu::yaml::parse_yaml_decoded(decoded).unwrap_or_else(|e| {
+        eprintln!("Error parsing {}: {}", path.display(), e);
+        std::process::exit(1);
+    })
+}
+
+/// A decoded input file and how it was stored
+struct InputFile {
+    decoded: Decoded,
+    compression: Option<Compression>,
+    /// Size on disk, compressed
+    file_size: u64,
+}
+
+/// Read a file, or a file inside an archive like `archive.zip!/data/users.json`, decompressing
+/// it if needed. Returns the contents, compression and compressed size.
+fn read_bytes(path: &Path) -> io::Result<(Vec<u8>, Option<Compression>, u64)> {
+    match jsdu::archive::split_member_path(path) {
+        Some((archive, member)) => {
+            let (archive, _) = jsdu::decompress::read(&archive)?;
+            let data = jsdu::archive::read_member(&archive, &member)?;
+            let file_size = data.len() as u64;
+            let (data, compression) = jsdu::decompress::decompress(data)?;
+            Ok((data, compression, file_size))
+        }
+        None => {
+            let (data, compression) = jsdu::decompress::read(path)?;
+            let file_size = fs::metadata(path).map(|x| x.len()).unwrap_or(0);
+            Ok((data, compression, file_size))
+        }
+    }
+}
+
+/// Read a file in UTF-8, UTF-16 or UTF-32, with or without byte order mark, and compressed with
+/// gzip, zstd, bzip2 or xz or not compressed
+fn read_input(path: &Path) -> InputFile {
+    let input =
+        read_bytes(path)
+            .map_err(|e| e.to_string())
+            .and_then(|(bytes, compression, file_size)| {
+                if let Some(format) = ArchiveFormat::detect(&bytes) {
+                    return Err(format!(
+                        "{} archive, select a file inside it with {}!/path/to/file.json",
+                        format,
+                        path.display()
+                    ));
+                }
+                Ok(InputFile {
+                    decoded: jsdu::encoding::decode(bytes)?,
+                    compression,
+                    file_size,
+                })
+            });
+    match input {
+        Ok(input) => input,
         Err(e) => {
             eprintln!("Error reading from {}: {}", path.display(), e);
             std::process::exit(1);
         }
+    }
+}
+
+/// Replace the input file with the output of a transform, encoded and compressed like the input
+/// unless `encoding`, `strip_bom` or `decompress` say otherwise. Prints "{verb} from A to B
+/// bytes".
+fn write_output(
+    path: &Path,
+    input: &InputFile,
+    output: String,
+    verb: &str,
+    encoding: Option<Encoding>,
+    strip_bom: bool,
+    decompress: bool,
+) {
+    if jsdu::archive::split_member_path(path).is_some() {
+        eprintln!(
+            "Error writing to {}: cannot modify files inside archives",
+            path.display()
+        );
+        std::process::exit(1);
+    }
+    let output = encode_output(&input.decoded, output, encoding, strip_bom);
+    println!(
+        "{} from {} to {} bytes",
+        verb,
+        input.decoded.encoded_len(),
+        output.len()
+    );
+    match input.compression {
+        Some(compression) if !decompress => {
+            let compressed = compression.compress(&output).unwrap_or_else(|e| {
+                eprintln!("Error compressing {}: {}", path.display(), e);
+                std::process::exit(1);
+            });
+            println!(
+                "{} file from {} to {} bytes",
+                compression,
+                input.file_size,
+                compressed.len()
+            );
+            write_file(path, compressed);
+        }
+        _ => write_file(path, output),
+    }
+}
+
+/// Encode the output of a transform like its input, or in `encoding`
+fn encode_output(
+    input: &Decoded,
+    output: String,
+    encoding: Option<Encoding>,
+    strip_bom: bool,
+) -> Vec<u8> {
+    let encoding = encoding.unwrap_or(input.encoding);
+    let bom = input.bom && !strip_bom;
+    if encoding == Encoding::Utf8 && !bom {
+        output.into_bytes()
+    } else {
+        encoding.encode(&output, bom)
+    }
+}
+
+/// The `--dialect` argument, or the dialect implied by the file extension
+fn input_dialect(path: &Path, dialect: Option<Dialect>) -> Dialect {
+    dialect
+        .or_else(|| Dialect::from_extension(path))
+        .unwrap_or_default()
+}
+
+fn write_file(path: &Path, contents: impl AsRef<[u8]>) {
+    if let Err(e) = fs::write(path, contents) {
+        eprintln!("Error writing to {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
+}
+
+fn show(
+    path: &Path,
+    json_pointer: Option<&str>,
+    relative_pointer: Option<&RelativeJsonPointer>,
+    expand: bool,
+    utf8_sizes: bool,
+    options: &DisplayOptions,
+) {
+    let input = read_input(path);
+    let decompressed_size = input.decoded.encoded_len();
+    let (encoding, bom) = (input.decoded.encoding, input.decoded.bom);
+    let mut decoded = input.decoded;
+    if utf8_sizes {
+        decoded.encoding = Encoding::Utf8;
+        decoded.bom = false;
+    }
+    let mut yaml_root = None;
+    let mut anchors = vec![];
+    let mut json = if jsdu::yaml::is_yaml_path(path) {
+        let yaml = parse_yaml(path, &decoded);
+        yaml_root = Some(yaml.js);
+        anchors = yaml.anchors;
+        yaml.json
+    } else {
+        std::mem::take(&mut decoded.text)
+    };
+    let mut json_pointer = json_pointer.map(str::to_string);
+    if expand {
+        let (inner_json, inner_pointer) = jsdu::payload::resolve_embedded(
+            json,
+            json_pointer.as_deref().unwrap_or(""),
+            options.dialect,
+        )
+        .expect("invalid JSON pointer");
+        json = inner_json;
+        json_pointer = Some(inner_pointer);
+    }
+
+    let root = match yaml_root {
+        Some(root) if !expand => root,
+        _ => {
+            // Embedded documents are always measured in UTF-8
+            if expand {
+                decoded.encoding = Encoding::Utf8;
+                decoded.bom = false;
+            }
+            decoded.text = json;
+            let root = parse_json(path, &decoded, Some(options.dialect));
+            json = decoded.text;
+            root
+        }
     };
+    let mut json_path = root
+        .json_pointer_path(&json, json_pointer.as_deref().unwrap_or(""))
+        .expect("invalid JSON pointer");
+    if let Some(relative_pointer) = relative_pointer {
+        match relative_pointer
+            .resolve(&root, &json, &json_path)
+            .expect("relative JSON pointer does not resolve to a value")
+        {
+            RelativeTarget::Value(path) => json_path = path,
+            RelativeTarget::Key(key) => {
+                println!("{:?}", key);
+                return;
+            }
+            RelativeTarget::Index(index) => {
+                println!("{}", index);
+                return;
+            }
+        }
+    }
+    let js = root.get_path(&json_path).unwrap();
+    for l in js.display_list(&json, options) {
+        println!("{}", l);
+    }
+    if !anchors.is_empty() && json_path.is_empty() {
+        println!("Anchors:");
+        for l in jsdu::yaml::display_anchors(&anchors) {
+            println!("{}", l);
+        }
+    }
+    if encoding != Encoding::Utf8 || bom {
+        let bom = if bom { " with BOM" } else { "" };
+        println!("Encoding: {}{}", encoding, bom);
+    }
+    if let Some(compression) = input.compression {
+        println!(
+            "Compressed with {}: {} bytes, {} bytes decompressed",
+            compression, input.file_size, decompressed_size
+        );
+    }
+    if !options.compressors.is_empty() {
+        println!(
+            "{}",
+            jsdu::compress::display_file_sizes(&json, &options.compressors)
+        );
+    }
+}
 
-    let mut js = &mut jsdu::size::JsonSize::new(&json);
+/// Format of `path` if it is an archive, looking only at its first bytes
+fn archive_format(path: &Path) -> Option<ArchiveFormat> {
+    if jsdu::archive::split_member_path(path).is_some() {
+        return None;
+    }
+    let (reader, _) = jsdu::decompress::open(path).ok()?;
+    let mut magic = vec![];
+    reader.take(512).read_to_end(&mut magic).ok()?;
+    ArchiveFormat::detect(&magic)
+}
+
+/// Size of each JSON file inside an archive
+fn show_archive(path: &Path, fields: usize, sort_by_size: bool) {
+    let members = jsdu::decompress::read(path)
+        .and_then(|(archive, _)| jsdu::archive::members(&archive))
+        .unwrap_or_else(|e| {
+            eprintln!("Error reading from {}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+    let parsed = jsdu::files::ParsedFiles::parse(
+        members
+            .into_iter()
+            .map(|member| (member.name, member.data))
+            .collect(),
+    );
+    for l in parsed.display_list(sort_by_size, fields) {
+        println!("{}", l);
+    }
+}
+
+fn show_directories(paths: &[PathBuf], fields: usize, sort_by_size: bool) {
+    let tree = jsdu::files::DirectoryTree::read(paths);
+    for l in tree.display_list(sort_by_size, fields) {
+        println!("{}", l);
+    }
+}
+
+fn show_follow(path: &Path, accounting: Accounting, interval: f64, from_start: bool) {
+    let exit_with_error = |e: io::Error| -> ! {
+        eprintln!("Error reading from {}: {}", path.display(), e);
+        std::process::exit(1);
+    };
+    let mut tail = Tail::new(path, from_start).unwrap_or_else(|e| exit_with_error(e));
+    let mut stats = FollowStats::new(accounting, 10);
+    let interval = Duration::from_secs_f64(interval);
+    let mut last_refresh = Instant::now();
+    loop {
+        for record in tail.poll().unwrap_or_else(|e| exit_with_error(e)) {
+            stats.add_record(&record);
+        }
+        let elapsed = last_refresh.elapsed();
+        if elapsed >= interval {
+            last_refresh = Instant::now();
+            if io::stdout().is_terminal() {
+                // Clear the screen
+                print!("\x1b[2J\x1b[H");
+            }
+            for l in stats.display_list(elapsed, 20) {
+                println!("{}", l);
+            }
+            println!();
+        }
+        if !tail.has_more() {
+            std::thread::sleep(Duration::from_millis(100).min(interval));
+        }
+    }
+}
+
+fn show_json_path(path: &Path, json_path: &JsonPath, utf8_sizes: bool, options: &DisplayOptions) {
+    let mut input = read_input(path).decoded;
+    if utf8_sizes {
+        input.encoding = Encoding::Utf8;
+        input.bom = false;
+    }
+    let (json, root) = if jsdu::yaml::is_yaml_path(path) {
+        let yaml = parse_yaml(path, &input);
+        (yaml.json, yaml.js)
+    } else {
+        let js = parse_json(path, &input, Some(options.dialect));
+        (input.text, js)
+    };
+    let matches = json_path.evaluate(&root, &json);
+    for l in jsdu::jsonpath::display_matches(&root, &json, &matches, options) {
+        println!("{}", l);
+    }
+    if !options.compressors.is_empty() {
+        println!(
+            "{}",
+            jsdu::compress::display_file_sizes(&json, &options.compressors)
+        );
+    }
+}
+
+fn schema(path: &Path, json_pointer: Option<&str>, limit: Option<usize>, accounting: Accounting) {
+    let (json, mut root) = read_json_size(path, None);
+
+    let mut js = &mut root;
+    if let Some(json_pointer) = json_pointer {
+        js = js
+            .index_json_pointer(&json, json_pointer)
+            .expect("invalid JSON pointer");
+    }
+    let mut schema = jsdu::schema::SchemaStats::new(accounting);
+    schema.add(js, &json);
+    for l in schema.display_list(limit) {
+        println!("{}", l);
+    }
+}
+
+fn keys(path: &Path, json_pointer: Option<&str>, limit: Option<usize>) {
+    let (json, mut root) = read_json_size(path, None);
+
+    let mut js = &mut root;
+    if let Some(json_pointer) = json_pointer {
+        js = js
+            .index_json_pointer(&json, json_pointer)
+            .expect("invalid JSON pointer");
+    }
+    let mut keys = jsdu::keys::KeyStats::new();
+    keys.add(js, &json);
+    for l in keys.display_list(limit) {
+        println!("{}", l);
+    }
+}
+
+fn stats(path: &Path, json_pointer: Option<&str>) {
+    let (json, mut root) = read_json_size(path, None);
+
+    let mut js = &mut root;
+    if let Some(json_pointer) = json_pointer {
+        js = js
+            .index_json_pointer(&json, json_pointer)
+            .expect("invalid JSON pointer");
+    }
+    let stats = jsdu::stats::Stats::new(js);
+    for l in stats.display_list(js, &json, json_pointer.unwrap_or("")) {
+        println!("{}", l);
+    }
+}
+
+fn top(path: &Path, json_pointer: Option<&str>, n: usize, containers: bool) {
+    let (json, root) = read_json_size(path, None);
+
+    let json_path = root
+        .json_pointer_path(&json, json_pointer.unwrap_or(""))
+        .expect("invalid JSON pointer");
+    let js = root.get_path(&json_path).unwrap();
+    let with_prefix = |values: Vec<(usize, Vec<usize>)>| -> Vec<(usize, Vec<usize>)> {
+        values
+            .into_iter()
+            .map(|(size, path)| (size, [&json_path[..], &path[..]].concat()))
+            .collect()
+    };
+
+    let leaves = with_prefix(jsdu::top::largest_leaves(js, n));
+    for l in jsdu::top::display_list(&root, &json, &leaves) {
+        println!("{}", l);
+    }
+    if containers {
+        println!();
+        println!("Largest containers:");
+        let containers = with_prefix(jsdu::top::largest_containers(js, n));
+        for l in jsdu::top::display_list(&root, &json, &containers) {
+            println!("{}", l);
+        }
+    }
+}
+
+fn arrays(
+    path: &Path,
+    json_pointer: Option<&str>,
+    min_len: usize,
+    outlier_factor: f64,
+    limit: Option<usize>,
+) {
+    let (json, root) = read_json_size(path, None);
+
+    let json_path = root
+        .json_pointer_path(&json, json_pointer.unwrap_or(""))
+        .expect("invalid JSON pointer");
+    let js = root.get_path(&json_path).unwrap();
+    let arrays = jsdu::distribution::array_distributions(js, json_path, min_len, outlier_factor);
+    for array in arrays.iter().take(limit.unwrap_or(usize::MAX)) {
+        for l in array.display_list(&root, &json, 10) {
+            println!("{}", l);
+        }
+        println!();
+    }
+}
+
+fn duplicates(path: &Path, json_pointer: Option<&str>, min_size: usize, limit: Option<usize>) {
+    let (json, root) = read_json_size(path, None);
+
+    let json_path = root
+        .json_pointer_path(&json, json_pointer.unwrap_or(""))
+        .expect("invalid JSON pointer");
+    let js = root.get_path(&json_path).unwrap();
+    let mut duplicates = jsdu::duplicates::find_duplicates(js, &json, min_size, 3);
+    duplicates.truncate(limit.unwrap_or(usize::MAX));
+    for duplicate in duplicates.iter_mut() {
+        for path in duplicate.paths.iter_mut() {
+            path.splice(0..0, json_path.iter().copied());
+        }
+    }
+    for l in jsdu::duplicates::display_list(&root, &json, &duplicates) {
+        println!("{}", l);
+    }
+}
+
+fn strings(path: &Path, json_pointer: Option<&str>, limit: Option<usize>) {
+    let (json, mut root) = read_json_size(path, None);
+
+    let mut js = &mut root;
     if let Some(json_pointer) = json_pointer {
         js = js
             .index_json_pointer(&json, json_pointer)
             .expect("invalid JSON pointer");
     }
-    for l in js.display_list(&json) {
+    let mut strings = jsdu::strings::StringStats::new();
+    strings.add(js, &json);
+    for l in strings.display_list(limit) {
         println!("{}", l);
     }
 }
+
+fn payloads(path: &Path, json_pointer: Option<&str>, limit: Option<usize>) {
+    let (json, mut root) = read_json_size(path, None);
+
+    let mut js = &mut root;
+    if let Some(json_pointer) = json_pointer {
+        js = js
+            .index_json_pointer(&json, json_pointer)
+            .expect("invalid JSON pointer");
+    }
+    let mut payloads = jsdu::payload::PayloadStats::new();
+    payloads.add(js, &json);
+    for l in payloads.display_list(limit) {
+        println!("{}", l);
+    }
+}
+
+fn numbers(path: &Path, json_pointer: Option<&str>, limit: Option<usize>, precision: Precision) {
+    let (json, mut root) = read_json_size(path, None);
+
+    let mut js = &mut root;
+    if let Some(json_pointer) = json_pointer {
+        js = js
+            .index_json_pointer(&json, json_pointer)
+            .expect("invalid JSON pointer");
+    }
+    let mut numbers = jsdu::numbers::NumberStats::new(precision);
+    numbers.add(js, &json);
+    for l in numbers.display_list(limit) {
+        println!("{}", l);
+    }
+}
+
+fn ndjson(path: &Path, view: NdjsonView, limit: usize, accounting: Accounting, concatenated: bool) {
+    let reader = match jsdu::archive::split_member_path(path) {
+        Some(_) => read_bytes(path).map(|(data, _, _)| {
+            let reader: Box<dyn io::BufRead> = Box::new(io::Cursor::new(data));
+            reader
+        }),
+        None => jsdu::decompress::open(path).map(|(reader, _)| reader),
+    };
+    let reader = match reader {
+        Ok(reader) => reader,
+        Err(e) => {
+            eprintln!("Error reading from {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let mut aggregate: Option<Box<dyn Aggregate>> = match view {
+        NdjsonView::Records => None,
+        NdjsonView::Schema => Some(Box::new(jsdu::schema::SchemaStats::new(accounting))),
+        NdjsonView::Keys => Some(Box::new(jsdu::keys::KeyStats::new())),
+        NdjsonView::Strings => Some(Box::new(jsdu::strings::StringStats::new())),
+        NdjsonView::Payloads => Some(Box::new(jsdu::payload::PayloadStats::new())),
+    };
+
+    let mut records = RecordStats::new(limit);
+    let mut reader = if concatenated {
+        RecordReader::concatenated(reader)
+    } else {
+        RecordReader::new(reader)
+    };
+    for record in reader.by_ref() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                eprintln!("Error reading from {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        };
+        if let Some((js, json)) = records.add_record(&record) {
+            if let Some(aggregate) = aggregate.as_mut() {
+                aggregate.add(&js, json);
+            }
+        }
+    }
+
+    if aggregate.is_some() && records.invalid_count > 0 {
+        eprintln!(
+            "Skipped {} invalid records out of {}",
+            records.invalid_count, records.records
+        );
+    }
+    let lines = match aggregate {
+        Some(aggregate) => aggregate.display_list(Some(limit)),
+        None => records.display_list(reader.bytes_read()),
+    };
+    for l in lines {
+        println!("{}", l);
+    }
+}
+
+fn locate(path: &Path, position: Position) {
+    let input = read_input(path).decoded;
+
+    // Byte offsets are in the encoding of the file
+    let offset = match position {
+        Position::Offset(offset) => input.text_offset(offset),
+        _ => position.to_offset(&input.text),
+    };
+    let offset = match offset {
+        Some(offset) => offset,
+        None => {
+            eprintln!("Position {:?} is outside of {}", position, path.display());
+            std::process::exit(1);
+        }
+    };
+    // Offsets of YAML files are positions in the YAML text, not in the converted JSON
+    let (json, root, location) = if jsdu::yaml::is_yaml_path(path) {
+        let yaml = parse_yaml(path, &input);
+        let location = yaml.locate(offset);
+        (yaml.json, yaml.js, location)
+    } else {
+        let root = parse_json(path, &input, None);
+        let location = jsdu::pointer::locate_offset(&root, offset);
+        (input.text, root, location)
+    };
+    let pointer = root.path_to_json_pointer(&json, &location.path).unwrap();
+    if location.in_key {
+        println!("{} (object key)", pointer);
+    } else {
+        println!("{}", pointer);
+    }
+}
+
+fn extract_blobs(
+    path: &Path,
+    json_pointer: Option<&str>,
+    out: &Path,
+    min_size: usize,
+    rewrite: bool,
+) {
+    if rewrite {
+        exit_if_yaml(path, "rewritten");
+    }
+    let (json, root) = read_json_size(path, None);
+    let json_path = root
+        .json_pointer_path(&json, json_pointer.unwrap_or(""))
+        .expect("invalid JSON pointer");
+    let js = root.get_path(&json_path).unwrap();
+    let blobs = jsdu::blobs::find_blobs(js, &json, json_path, min_size);
+    if blobs.is_empty() {
+        println!("no blobs found");
+        return;
+    }
+
+    if let Err(e) = fs::create_dir_all(out) {
+        eprintln!("Error creating {}: {}", out.display(), e);
+        std::process::exit(1);
+    }
+    let mut used = HashSet::new();
+    let mut references = vec![];
+    for blob in blobs.iter() {
+        let pointer = root.path_to_json_pointer(&json, &blob.path).unwrap();
+        let name = jsdu::blobs::file_name(&pointer, blob.extension(), &mut used);
+        let file = out.join(name);
+        write_file(&file, &blob.data);
+        println!("{:12} {} -> {}", blob.data.len(), pointer, file.display());
+        references.push(file.display().to_string());
+    }
+
+    if rewrite {
+        let rewritten = jsdu::blobs::replace_blobs(&json, &blobs, &references);
+        write_output(
+            path,
+            &read_input(path),
+            rewritten,
+            "rewrote",
+            None,
+            false,
+            false,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_significant_digits() {
+        for command in ["numbers", "round"] {
+            let args =
+                |n: &str| Args::try_parse_from(["jsdu", command, "n.json", "--significant", n]);
+            assert!(args("0").is_err());
+            assert!(args("1").is_ok());
+        }
+    }
+
+    #[test]
+    fn test_path_conflicts() {
+        let args = |flags: &[&str]| {
+            let args = ["jsdu", "show", "p.json", "--path", "$.a"];
+            Args::try_parse_from(args.iter().chain(flags))
+        };
+        assert!(args(&["--compress", "gzip"]).is_ok());
+        for flag in [
+            &["--breakdown"][..],
+            &["--entropy"],
+            &["--memory"],
+            &["--sort", "size"],
+        ] {
+            assert!(args(flag).is_err());
+        }
+    }
+}
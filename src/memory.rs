@@ -0,0 +1,122 @@
+//! Estimate how much memory a JSON value uses once deserialized into a DOM, modeled after
+//! `serde_json::Value` with default features on a 64-bit target:
+//!
+//! * Every `Value` is 32 bytes: the largest variant is `String` (24 bytes) plus the tag. Null,
+//!   booleans and numbers have no heap allocation.
+//! * Strings allocate their unescaped length.
+//! * Arrays are a `Vec<Value>` grown by pushing items, so the capacity is the next power of two,
+//!   4 at least.
+//! * Objects are a `BTreeMap<String, Value>`. Each key is a `String` that allocates its unescaped
+//!   length. B-tree nodes store up to 11 keys (24 bytes each) and values (32 bytes each), leaf
+//!   nodes take 632 bytes and internal nodes 728. Keys are not inserted in order, so nodes are
+//!   assumed to be 2/3 full on average.
+//!
+//! Allocator overhead and fragmentation are not included, real usage will be somewhat higher.
+use crate::size::{unescape_json_string, JsonSize, JsonValueKind};
+
+const VALUE_SIZE: usize = 32;
+const BTREE_CAPACITY: usize = 11;
+const BTREE_LEAF_SIZE: usize = 632;
+const BTREE_INTERNAL_SIZE: usize = 728;
+/// Average number of entries per node
+const BTREE_FILL: usize = BTREE_CAPACITY * 2 / 3;
+
+/// Bytes used by the value, including the `Value` itself
+pub fn footprint(js: &JsonSize, json: &str) -> usize {
+    VALUE_SIZE + heap_size(js, json)
+}
+
+/// Like `footprint`, plus the allocation of the key for object members. B-tree nodes are not
+/// included, they belong to the parent object.
+pub fn member_footprint(js: &JsonSize, json: &str) -> usize {
+    let key = js.key().get_key_str(json).map_or(0, unescaped_len);
+    key + footprint(js, json)
+}
+
+/// Bytes allocated by the value, not including the `Value` itself
+pub fn heap_size(js: &JsonSize, json: &str) -> usize {
+    match js.value_kind() {
+        JsonValueKind::Null | JsonValueKind::Boolean | JsonValueKind::Number => 0,
+        JsonValueKind::String => {
+            let span = js.span();
+            unescaped_len(&json[span.start + 1..span.end - 1])
+        }
+        JsonValueKind::Array => {
+            let items: usize = js.children().iter().map(|x| heap_size(x, json)).sum();
+            vec_capacity(js.children().len()) * VALUE_SIZE + items
+        }
+        JsonValueKind::Object => {
+            let members: usize = js
+                .children()
+                .iter()
+                .map(|x| unescaped_len(x.key().get_key_str(json).unwrap()) + heap_size(x, json))
+                .sum();
+            btree_size(js.children().len()) + members
+        }
+    }
+}
+
+fn unescaped_len(raw: &str) -> usize {
+    if raw.contains('\\') {
+        unescape_json_string(raw).len()
+    } else {
+        raw.len()
+    }
+}
+
+fn vec_capacity(len: usize) -> usize {
+    match len {
+        0 => 0,
+        1..=4 => 4,
+        _ => len.next_power_of_two(),
+    }
+}
+
+fn btree_size(len: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    if len <= BTREE_CAPACITY {
+        return BTREE_LEAF_SIZE;
+    }
+    let leaves = len.div_ceil(BTREE_FILL);
+    // Each internal node has one more child than entries
+    let mut internal = 0;
+    let mut level = leaves;
+    while level > 1 {
+        level = level.div_ceil(BTREE_FILL + 1);
+        internal += level;
+    }
+
+    leaves * BTREE_LEAF_SIZE + internal * BTREE_INTERNAL_SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn footprint_of(json: &str) -> usize {
+        footprint(&JsonSize::new(json), json)
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(footprint_of("1234567890.5"), 32);
+        assert_eq!(footprint_of("null"), 32);
+        assert_eq!(footprint_of(r#""abc""#), 32 + 3);
+        assert_eq!(footprint_of(r#""a\nbé""#), 32 + 5);
+    }
+
+    #[test]
+    fn test_containers() {
+        assert_eq!(footprint_of("[]"), 32);
+        assert_eq!(footprint_of("[1, 2, 3]"), 32 + 4 * 32);
+        assert_eq!(footprint_of("[1, 2, 3, 4, 5]"), 32 + 8 * 32);
+        assert_eq!(footprint_of(r#"{"ab": "cde"}"#), 32 + 632 + 2 + 3);
+        assert_eq!(btree_size(14), 2 * 632 + 728);
+
+        let json = r#"{"ab": "cde"}"#;
+        let js = JsonSize::new(json);
+        assert_eq!(member_footprint(&js.children()[0], json), 2 + 32 + 3);
+    }
+}
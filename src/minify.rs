@@ -1,19 +1,51 @@
+use crate::size::Dialect;
+use std::iter::Peekable;
+
 pub fn minify(json: &str) -> String {
     MinifyIter::new(json).collect()
 }
 
-pub struct MinifyIter<I> {
-    chars: I,
+/// Minify a JSONC or JSON5 file. Comments are removed unless `keep_comments` is set, in that case
+/// the newline that ends a line comment is also kept.
+pub fn minify_dialect(json: &str, dialect: Dialect, keep_comments: bool) -> String {
+    MinifyIter::with_dialect(json, dialect, keep_comments).collect()
+}
+
+pub struct MinifyIter<I: Iterator> {
+    chars: Peekable<I>,
     in_string: bool,
     escape_next: bool,
+    /// Quote that started the current string, `'` is only allowed in JSON5
+    quote: char,
+    dialect: Dialect,
+    keep_comments: bool,
+    comment: Option<Comment>,
+    /// Second character of `//` or `/*`
+    pending: Option<char>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Comment {
+    Line,
+    /// The bool is true if the previous character was `*`
+    Block(bool),
 }
 
 impl<'a> MinifyIter<std::str::Chars<'a>> {
     pub fn new(json: &'a str) -> Self {
+        Self::with_dialect(json, Dialect::Json, false)
+    }
+
+    pub fn with_dialect(json: &'a str, dialect: Dialect, keep_comments: bool) -> Self {
         MinifyIter {
-            chars: json.chars(),
+            chars: json.chars().peekable(),
             in_string: false,
             escape_next: false,
+            quote: '"',
+            dialect,
+            keep_comments,
+            comment: None,
+            pending: None,
         }
     }
 }
@@ -22,6 +54,10 @@ impl<I: Iterator<Item = char>> Iterator for MinifyIter<I> {
     type Item = char;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(c) = self.pending.take() {
+            return Some(c);
+        }
+
         while let Some(c) = self.chars.next() {
             if self.escape_next {
                 self.escape_next = false;
@@ -29,9 +65,29 @@ impl<I: Iterator<Item = char>> Iterator for MinifyIter<I> {
                 return Some(c);
             }
 
+            if let Some(comment) = self.comment {
+                self.comment = match (comment, c) {
+                    (Comment::Line, '\n') => None,
+                    (Comment::Block(true), '/') => None,
+                    (Comment::Block(_), c) => Some(Comment::Block(c == '*')),
+                    (Comment::Line, _) => Some(Comment::Line),
+                };
+                if self.keep_comments {
+                    return Some(c);
+                }
+                continue;
+            }
+
             match c {
-                '"' => {
-                    self.in_string = !self.in_string;
+                '"' | '\'' if !self.in_string => {
+                    if c == '"' || self.dialect == Dialect::Json5 {
+                        self.in_string = true;
+                        self.quote = c;
+                    }
+                    return Some(c);
+                }
+                c if c == self.quote && self.in_string => {
+                    self.in_string = false;
                     return Some(c);
                 }
                 '\\' if self.in_string => {
@@ -40,6 +96,19 @@ impl<I: Iterator<Item = char>> Iterator for MinifyIter<I> {
                     // Return the escape character
                     return Some(c);
                 }
+                '/' if !self.in_string && self.dialect.allows_comments() => {
+                    let next = self.chars.peek().copied();
+                    self.comment = match next {
+                        Some('/') => Some(Comment::Line),
+                        Some('*') => Some(Comment::Block(false)),
+                        _ => return Some(c),
+                    };
+                    self.chars.next();
+                    if self.keep_comments {
+                        self.pending = next;
+                        return Some(c);
+                    }
+                }
                 // Skip whitespace outside of strings
                 c if c.is_whitespace() && !self.in_string => continue,
                 _ => return Some(c),
@@ -77,4 +146,23 @@ mod tests {
             r#"{"escaped":"Contains \\\"escaped\\\" characters and \"whitespace inside quotes\""}"#
         );
     }
+
+    #[test]
+    fn it_strips_or_keeps_comments() {
+        let json = "{\n  // line\n  a: 'x y', /* block */\n  \"b//\": [1, 2,],\n}";
+
+        assert_eq!(
+            minify_dialect(json, Dialect::Json5, false),
+            r#"{a:'x y',"b//":[1,2,],}"#
+        );
+        assert_eq!(
+            minify_dialect(json, Dialect::Json5, true),
+            "{// line\na:'x y',/* block */\"b//\":[1,2,],}"
+        );
+        // Single quotes only start strings in JSON5
+        assert_eq!(
+            minify_dialect("[\"'\", 1 ]", Dialect::Jsonc, false),
+            "[\"'\",1]"
+        );
+    }
 }
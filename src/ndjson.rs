@@ -0,0 +1,445 @@
+//! Read newline delimited JSON (JSON Lines), or any stream of concatenated JSON values, one
+//! record at a time. Records are never all in memory, so the input can be bigger than RAM.
+use crate::keys::KeyStats;
+use crate::payload::PayloadStats;
+use crate::schema::SchemaStats;
+use crate::size::{percent_of, size_bar, JsonSize};
+use crate::strings::StringStats;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{self, BufRead};
+
+/// One top-level value of the stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Position in the stream, starting at 0
+    pub index: usize,
+    /// Byte offset of the first byte of the record
+    pub offset: usize,
+    /// Line of the first byte of the record, starting at 1
+    pub line: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl Record {
+    /// `None` if the record is not valid UTF-8
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.bytes).ok()
+    }
+}
+
+/// Splits a stream into top-level values, without parsing them. Values can be separated by
+/// newlines, other whitespace, or nothing at all (`{"a":1}{"a":2}`). Invalid JSON is returned as
+/// is, the parser will reject it.
+pub struct RecordReader<R> {
+    reader: R,
+    /// A newline always ends the record, so one truncated line can not swallow the rest
+    json_lines: bool,
+    index: usize,
+    offset: usize,
+    line: usize,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    /// JSON Lines: a value can not span more than one line, and a line that does not start with
+    /// a value is one invalid record
+    pub fn new(reader: R) -> Self {
+        RecordReader {
+            reader,
+            json_lines: true,
+            index: 0,
+            offset: 0,
+            line: 1,
+        }
+    }
+
+    /// Concatenated values, that can span many lines like pretty printed JSON
+    pub fn concatenated(reader: R) -> Self {
+        RecordReader {
+            json_lines: false,
+            ..Self::new(reader)
+        }
+    }
+
+    /// Bytes read so far, including whitespace between records
+    pub fn bytes_read(&self) -> usize {
+        self.offset
+    }
+
+    /// Skip a UTF-8 byte order mark at the start of the stream
+    fn skip_bom(&mut self) -> io::Result<()> {
+        const BOM: &[u8] = b"\xEF\xBB\xBF";
+        if self.reader.fill_buf()?.starts_with(BOM) {
+            self.reader.consume(BOM.len());
+            self.offset += BOM.len();
+        }
+        Ok(())
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<Record>> {
+        if self.offset == 0 {
+            self.skip_bom()?;
+        }
+        let mut record: Option<Record> = None;
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escape_next = false;
+        let mut in_scalar = false;
+
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            let mut consumed = 0;
+            let mut done = false;
+            for &b in buf {
+                let record = match &mut record {
+                    Some(record) => record,
+                    None if b.is_ascii_whitespace() => {
+                        consumed += 1;
+                        self.offset += 1;
+                        if b == b'\n' {
+                            self.line += 1;
+                        }
+                        continue;
+                    }
+                    None => record.insert(Record {
+                        index: self.index,
+                        offset: self.offset,
+                        line: self.line,
+                        bytes: vec![],
+                    }),
+                };
+
+                // In JSON Lines the rest of the line is part of the scalar, even if it is not valid
+                if in_scalar
+                    && !self.json_lines
+                    && (b.is_ascii_whitespace() || matches!(b, b'"' | b'[' | b'{' | b']' | b'}'))
+                {
+                    // End of a top-level number or keyword, this byte belongs to the next record
+                    done = true;
+                    break;
+                }
+
+                consumed += 1;
+                self.offset += 1;
+                if b == b'\n' {
+                    self.line += 1;
+                    if self.json_lines {
+                        // End of a scalar, or unclosed brackets or string, an invalid record
+                        done = true;
+                        break;
+                    }
+                }
+                record.bytes.push(b);
+
+                if in_string {
+                    if escape_next {
+                        escape_next = false;
+                    } else if b == b'\\' {
+                        escape_next = true;
+                    } else if b == b'"' {
+                        in_string = false;
+                        done = depth == 0;
+                    }
+                } else {
+                    match b {
+                        b'"' => in_string = true,
+                        b'[' | b'{' => depth += 1,
+                        b']' | b'}' => {
+                            depth = depth.saturating_sub(1);
+                            done = depth == 0;
+                        }
+                        _ if depth == 0 => in_scalar = true,
+                        _ => {}
+                    }
+                }
+                if done {
+                    break;
+                }
+            }
+            self.reader.consume(consumed);
+            if done {
+                break;
+            }
+        }
+
+        if record.is_some() {
+            self.index += 1;
+        }
+        Ok(record)
+    }
+}
+
+impl<R: BufRead> Iterator for RecordReader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// A report that can be fed many JSON values and printed at the end
+pub trait Aggregate {
+    fn add(&mut self, js: &JsonSize, json: &str);
+    fn display_list(&self, limit: Option<usize>) -> Vec<String>;
+}
+
+impl Aggregate for SchemaStats {
+    fn add(&mut self, js: &JsonSize, json: &str) {
+        SchemaStats::add(self, js, json)
+    }
+
+    fn display_list(&self, limit: Option<usize>) -> Vec<String> {
+        SchemaStats::display_list(self, limit)
+    }
+}
+
+impl Aggregate for KeyStats {
+    fn add(&mut self, js: &JsonSize, json: &str) {
+        KeyStats::add(self, js, json)
+    }
+
+    fn display_list(&self, limit: Option<usize>) -> Vec<String> {
+        KeyStats::display_list(self, limit)
+    }
+}
+
+impl Aggregate for StringStats {
+    fn add(&mut self, js: &JsonSize, json: &str) {
+        StringStats::add(self, js, json)
+    }
+
+    fn display_list(&self, limit: Option<usize>) -> Vec<String> {
+        StringStats::display_list(self, limit)
+    }
+}
+
+impl Aggregate for PayloadStats {
+    fn add(&mut self, js: &JsonSize, json: &str) {
+        PayloadStats::add(self, js, json)
+    }
+
+    fn display_list(&self, limit: Option<usize>) -> Vec<String> {
+        PayloadStats::display_list(self, limit)
+    }
+}
+
+/// Location of a record, to report it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RecordId {
+    pub index: usize,
+    pub line: usize,
+}
+
+impl RecordId {
+    fn to_display(self) -> String {
+        format!("#{} (line {})", self.index, self.line)
+    }
+}
+
+/// Sizes of the records of a stream, as if they were the items of a virtual root array. Only
+/// the largest records are kept.
+#[derive(Debug)]
+pub struct RecordStats {
+    /// How many records to keep
+    max_largest: usize,
+    /// Min-heap with the largest records
+    largest: BinaryHeap<Reverse<(usize, RecordId)>>,
+    /// First invalid records
+    invalid: Vec<RecordId>,
+    pub invalid_count: usize,
+    pub records: usize,
+    pub record_bytes: usize,
+    pub min: usize,
+    pub max: usize,
+}
+
+impl RecordStats {
+    pub fn new(max_largest: usize) -> Self {
+        RecordStats {
+            max_largest,
+            largest: BinaryHeap::new(),
+            invalid: vec![],
+            invalid_count: 0,
+            records: 0,
+            record_bytes: 0,
+            min: usize::MAX,
+            max: 0,
+        }
+    }
+
+    /// Parse a record and count it. Returns the parsed value, or `None` if it is not valid JSON.
+    pub fn add_record<'a>(&mut self, record: &'a Record) -> Option<(JsonSize, &'a str)> {
+        let id = RecordId {
+            index: record.index,
+            line: record.line,
+        };
+        let size = record.bytes.len();
+        self.records += 1;
+        self.record_bytes += size;
+        self.min = self.min.min(size);
+        self.max = self.max.max(size);
+        self.largest.push(Reverse((size, id)));
+        if self.largest.len() > self.max_largest {
+            self.largest.pop();
+        }
+
+        let parsed = record
+            .text()
+            .and_then(|text| Some((JsonSize::try_new(text)?, text)));
+        if parsed.is_none() {
+            self.invalid_count += 1;
+            if self.invalid.len() < 10 {
+                self.invalid.push(id);
+            }
+        }
+
+        parsed
+    }
+
+    /// Format like `JsonSize::display_list`, with the largest records as children. `total` is
+    /// the size of the stream, including whitespace between records.
+    pub fn display_list(&self, total: usize) -> Vec<String> {
+        let mut lines = vec![format!("{:12} [##########] Total", total)];
+        let mut largest: Vec<_> = self.largest.iter().map(|Reverse(x)| *x).collect();
+        largest.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        for (size, id) in largest {
+            lines.push(format!(
+                "{:12} {} {}",
+                size,
+                size_bar(size, total),
+                id.to_display()
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(format!(
+            "{} records, {:.1}% of {} bytes. Record size: min {}, avg {}, max {}",
+            self.records,
+            percent_of(self.record_bytes, total),
+            total,
+            if self.records == 0 { 0 } else { self.min },
+            self.record_bytes.checked_div(self.records).unwrap_or(0),
+            self.max
+        ));
+        if self.invalid_count > 0 {
+            let invalid: Vec<_> = self.invalid.iter().map(|x| x.to_display()).collect();
+            lines.push(format!(
+                "{} invalid records: {}{}",
+                self.invalid_count,
+                invalid.join(", "),
+                if self.invalid_count > self.invalid.len() {
+                    ", ..."
+                } else {
+                    ""
+                }
+            ));
+        }
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(input: &str) -> Vec<(usize, String)> {
+        // Small buffer to test records split between reads
+        let reader = io::BufReader::with_capacity(3, input.as_bytes());
+        RecordReader::new(reader)
+            .map(|record| {
+                let record = record.unwrap();
+                (record.line, record.text().unwrap().to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let input = "{\"a\": \"}\\\"\"}\n[1, [2]]\n\n\"x\"\n  12\ntrue\n";
+        assert_eq!(
+            records(input),
+            vec![
+                (1, "{\"a\": \"}\\\"\"}".to_string()),
+                (2, "[1, [2]]".to_string()),
+                (4, "\"x\"".to_string()),
+                (5, "12".to_string()),
+                (6, "true".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_concatenated_values() {
+        let input = "{\"a\":\n1}{\"a\":2}\"s\"3 4[]";
+        let reader = io::BufReader::with_capacity(3, input.as_bytes());
+        let records: Vec<_> = RecordReader::concatenated(reader)
+            .map(|record| {
+                let record = record.unwrap();
+                (record.line, record.text().unwrap().to_string())
+            })
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                (1, "{\"a\":\n1}".to_string()),
+                (2, "{\"a\":2}".to_string()),
+                (2, "\"s\"".to_string()),
+                (2, "3".to_string()),
+                (2, "4".to_string()),
+                (2, "[]".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncated_line() {
+        let input = "{\"a\": [1\n{\"b\": \"x\n{\"c\": 3}\nnot json\n";
+        assert_eq!(
+            records(input),
+            vec![
+                (1, "{\"a\": [1".to_string()),
+                (2, "{\"b\": \"x".to_string()),
+                (3, "{\"c\": 3}".to_string()),
+                (4, "not json".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bom() {
+        let input = "\u{FEFF}{\"a\": 1}\n{\"a\": 2}\n";
+        assert_eq!(
+            records(input),
+            vec![(1, "{\"a\": 1}".to_string()), (2, "{\"a\": 2}".to_string())]
+        );
+        let mut reader = RecordReader::new(input.as_bytes());
+        assert_eq!(reader.next().unwrap().unwrap().offset, 3);
+    }
+
+    #[test]
+    fn test_record_stats() {
+        let input = "{\"a\": 1}\nnot json\n[1, 2, 3]\n{\"a\": \n";
+        let mut stats = RecordStats::new(2);
+        let mut schema = SchemaStats::new(crate::size::Accounting::Value);
+        for record in RecordReader::new(input.as_bytes()) {
+            let record = record.unwrap();
+            if let Some((js, text)) = stats.add_record(&record) {
+                Aggregate::add(&mut schema, &js, text);
+            }
+        }
+
+        assert_eq!(stats.records, 4);
+        assert_eq!(stats.invalid_count, 2);
+        assert_eq!(stats.max, 9);
+        assert_eq!(schema.sorted().len(), 2);
+        let lines = stats.display_list(input.len());
+        assert_eq!(
+            lines[1],
+            format!("{:12} {} #2 (line 3)", 9, size_bar(9, input.len()))
+        );
+    }
+}
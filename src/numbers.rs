@@ -0,0 +1,408 @@
+//! Report how much precision numbers carry, and round them to fewer digits without touching the
+//! rest of the file.
+use crate::size::{percent_of, size_bar, JsonSize, JsonValueKind};
+use std::collections::HashMap;
+
+/// How many digits to keep when rounding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    /// Digits after the decimal point
+    Decimals(u32),
+    /// Digits starting from the first non-zero digit
+    Significant(u32),
+}
+
+impl std::fmt::Display for Precision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Precision::Decimals(n) => write!(f, "{} decimals", n),
+            Precision::Significant(n) => write!(f, "{} significant digits", n),
+        }
+    }
+}
+
+/// Why a number can not be rounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unroundable {
+    /// The exponent does not fit in an i64, like `1e99999999999999999999`
+    ExponentOverflow,
+    /// JSON5 hexadecimal numbers, `Infinity` and `NaN`
+    NotDecimal,
+}
+
+/// A JSON number split into its parts: `-12.50e3` is negative, digits "1250", 2 fraction digits
+/// and exponent "e3".
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Number<'a> {
+    negative: bool,
+    /// Integer and fraction digits
+    digits: String,
+    fraction_len: usize,
+    /// Exponent as written, including the `e`
+    exponent_str: &'a str,
+    exponent: i64,
+}
+
+impl<'a> Number<'a> {
+    fn parse(text: &'a str) -> Result<Self, Unroundable> {
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(x) => (true, x),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (mantissa, exponent_str) = match text.find(['e', 'E']) {
+            Some(i) => text.split_at(i),
+            None => (text, ""),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let is_digits = |x: &str| x.bytes().all(|b| b.is_ascii_digit());
+        if !is_digits(integer) || !is_digits(fraction) || mantissa == "." {
+            return Err(Unroundable::NotDecimal);
+        }
+        let exponent = match exponent_str.len() {
+            0 => 0,
+            _ => exponent_str[1..]
+                .parse()
+                .map_err(|_| Unroundable::ExponentOverflow)?,
+        };
+
+        Ok(Number {
+            negative,
+            digits: format!("{}{}", integer, fraction),
+            fraction_len: fraction.len(),
+            exponent_str,
+            exponent,
+        })
+    }
+
+    /// Digits after the decimal point once the exponent is applied
+    fn decimals(&self) -> usize {
+        (self.fraction_len as i64)
+            .saturating_sub(self.exponent)
+            .max(0) as usize
+    }
+
+    fn significant_digits(&self) -> usize {
+        self.digits.trim_start_matches('0').len()
+    }
+
+    /// Round half away from zero. Returns `None` if the number already fits in `precision`.
+    fn round(&self, precision: Precision) -> Option<String> {
+        let len = self.digits.len() as i64;
+        // Number of leading digits to keep
+        let keep = match precision {
+            Precision::Decimals(d) => (len - self.fraction_len as i64)
+                .saturating_add(self.exponent)
+                .saturating_add(d as i64),
+            Precision::Significant(n) => {
+                (self.digits.len() - self.significant_digits()) as i64 + n as i64
+            }
+        };
+        if keep >= len {
+            return None;
+        }
+
+        let mut digits: Vec<u8> = self.digits.as_bytes()[..keep.max(0) as usize].to_vec();
+        if keep >= 0 && self.digits.as_bytes()[keep as usize] >= b'5' {
+            // Add one to the last kept digit
+            let mut i = digits.len();
+            loop {
+                if i == 0 {
+                    digits.insert(0, b'1');
+                    break;
+                }
+                i -= 1;
+                if digits[i] == b'9' {
+                    digits[i] = b'0';
+                } else {
+                    digits[i] += 1;
+                    break;
+                }
+            }
+        }
+        // The dropped digits are zeros now
+        let mut fraction_len = self.fraction_len as i64 - (len - keep.max(0));
+        if fraction_len < 0 {
+            digits.extend(std::iter::repeat_n(b'0', (-fraction_len) as usize));
+            fraction_len = 0;
+        }
+        let mut fraction_len = fraction_len as usize;
+        while fraction_len > 0 && digits.last() == Some(&b'0') {
+            digits.pop();
+            fraction_len -= 1;
+        }
+        if digits.iter().all(|&d| d == b'0') {
+            return Some("0".to_string());
+        }
+
+        let (integer, fraction) = digits.split_at(digits.len() - fraction_len);
+        let integer = std::str::from_utf8(integer)
+            .unwrap()
+            .trim_start_matches('0');
+        let mut rounded = String::new();
+        if self.negative {
+            rounded.push('-');
+        }
+        rounded.push_str(if integer.is_empty() { "0" } else { integer });
+        if !fraction.is_empty() {
+            rounded.push('.');
+            rounded.push_str(std::str::from_utf8(fraction).unwrap());
+        }
+        rounded.push_str(self.exponent_str);
+
+        Some(rounded)
+    }
+}
+
+/// Round a JSON number, returns `None` if it already fits in `precision`
+pub fn round_number(text: &str, precision: Precision) -> Option<String> {
+    Number::parse(text).ok()?.round(precision)
+}
+
+/// Statistics of the numbers found at one generalized path
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NumberPathStats {
+    pub count: usize,
+    pub bytes: usize,
+    pub max_decimals: usize,
+    pub max_significant: usize,
+    /// Numbers that have more digits than the precision
+    pub imprecise: usize,
+    /// Numbers that can not be rounded, they are not counted in the other columns
+    pub unroundable: usize,
+    /// Bytes saved by rounding all the numbers to the precision
+    pub savings: usize,
+}
+
+/// Accumulates `NumberPathStats` for every generalized path (see `schema`). Can be fed more than
+/// one tree.
+#[derive(Debug)]
+pub struct NumberStats {
+    precision: Precision,
+    paths: HashMap<String, NumberPathStats>,
+    /// Sum of the sizes of all the trees
+    total: usize,
+}
+
+impl NumberStats {
+    pub fn new(precision: Precision) -> Self {
+        Self {
+            precision,
+            paths: HashMap::new(),
+            total: 0,
+        }
+    }
+
+    pub fn add(&mut self, js: &JsonSize, json: &str) {
+        self.total += js.total_size();
+        let mut path = String::new();
+        self.add_value(js, json, &mut path);
+    }
+
+    fn add_value(&mut self, js: &JsonSize, json: &str, path: &mut String) {
+        if js.value_kind() == JsonValueKind::Number {
+            let text = &json[js.span()];
+            let stats = match self.paths.get_mut(path.as_str()) {
+                Some(stats) => stats,
+                None => self.paths.entry(path.clone()).or_default(),
+            };
+            stats.count += 1;
+            stats.bytes += text.len();
+            let Ok(number) = Number::parse(text) else {
+                stats.unroundable += 1;
+                return;
+            };
+            stats.max_decimals = stats.max_decimals.max(number.decimals());
+            stats.max_significant = stats.max_significant.max(number.significant_digits());
+            if let Some(rounded) = number.round(self.precision) {
+                stats.imprecise += 1;
+                stats.savings += text.len().saturating_sub(rounded.len());
+            }
+            return;
+        }
+
+        let path_len = path.len();
+        for child in js.children() {
+            path.push('/');
+            match js.value_kind() {
+                JsonValueKind::Array => path.push('*'),
+                _ => path.push_str(&child.key().to_json_pointer_token(json)),
+            }
+            self.add_value(child, json, path);
+            path.truncate(path_len);
+        }
+    }
+
+    /// All the generalized paths with numbers, most savings first
+    pub fn sorted(&self) -> Vec<(&str, &NumberPathStats)> {
+        let mut paths: Vec<_> = self
+            .paths
+            .iter()
+            .map(|(path, stats)| (path.as_str(), stats))
+            .collect();
+        paths.sort_by(|a, b| {
+            b.1.savings
+                .cmp(&a.1.savings)
+                .then_with(|| b.1.bytes.cmp(&a.1.bytes))
+                .then_with(|| a.0.cmp(b.0))
+        });
+        paths
+    }
+
+    /// Format as a table, showing at most `limit` paths, followed by the sum of all paths
+    pub fn display_list(&self, limit: Option<usize>) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{:>12} {:12} {:>10} {:>12} {:>8} {:>8} {:>10} Path",
+            "Savings", "", "Count", "Bytes", "Max dec", "Max sig", "Imprecise"
+        )];
+        let sorted = self.sorted();
+        for (path, stats) in sorted.iter().take(limit.unwrap_or(usize::MAX)) {
+            lines.push(format!(
+                "{:12} {} {:10} {:12} {:8} {:8} {:10} {}",
+                stats.savings,
+                size_bar(stats.savings, self.total),
+                stats.count,
+                stats.bytes,
+                stats.max_decimals,
+                stats.max_significant,
+                stats.imprecise,
+                path
+            ));
+        }
+
+        let savings: usize = sorted.iter().map(|(_, stats)| stats.savings).sum();
+        let imprecise: usize = sorted.iter().map(|(_, stats)| stats.imprecise).sum();
+        let count: usize = sorted.iter().map(|(_, stats)| stats.count).sum();
+        let unroundable: usize = sorted.iter().map(|(_, stats)| stats.unroundable).sum();
+        let unroundable = if unroundable > 0 {
+            format!(" ({} can not be rounded)", unroundable)
+        } else {
+            String::new()
+        };
+        lines.push(format!(
+            "{:12} {} Total: {} of {} numbers{} have more than {}, rounding saves {:.1}% of {} bytes",
+            savings,
+            size_bar(savings, self.total),
+            imprecise,
+            count,
+            unroundable,
+            self.precision,
+            percent_of(savings, self.total),
+            self.total
+        ));
+
+        lines
+    }
+}
+
+/// Round every number inside `nodes` (which can be nested) and leave the rest of the file as
+/// it is
+pub fn round_numbers(json: &str, nodes: &[&JsonSize], precision: Precision) -> String {
+    let mut spans = vec![];
+    let mut stack = nodes.to_vec();
+    while let Some(node) = stack.pop() {
+        if node.value_kind() == JsonValueKind::Number {
+            spans.push(node.span());
+        }
+        stack.extend(node.children());
+    }
+    spans.sort_by_key(|span| span.start);
+    spans.dedup();
+
+    let mut out = String::with_capacity(json.len());
+    let mut last = 0;
+    for span in spans {
+        if let Some(rounded) = round_number(&json[span.clone()], precision) {
+            out.push_str(&json[last..span.start]);
+            out.push_str(&rounded);
+            last = span.end;
+        }
+    }
+    out.push_str(&json[last..]);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_decimals() {
+        let round = |x| round_number(x, Precision::Decimals(2));
+        assert_eq!(round("0.30000000000000004"), Some("0.3".to_string()));
+        assert_eq!(round("-122.419415"), Some("-122.42".to_string()));
+        assert_eq!(round("9.999"), Some("10".to_string()));
+        assert_eq!(round("0.004"), Some("0".to_string()));
+        assert_eq!(round("1.5e-3"), Some("0".to_string()));
+        assert_eq!(round("1.23456e1"), Some("1.235e1".to_string()));
+        assert_eq!(round("1.25"), None);
+        assert_eq!(round("100"), None);
+        assert_eq!(round("1e9223372036854775807"), None);
+        assert_eq!(round("1.5e-9223372036854775808"), Some("0".to_string()));
+        assert_eq!(round("1e99999999999999999999"), None);
+        assert_eq!(round("0x1F"), None);
+    }
+
+    #[test]
+    fn test_round_significant() {
+        let round = |x| round_number(x, Precision::Significant(3));
+        assert_eq!(round("0.00123456"), Some("0.00123".to_string()));
+        assert_eq!(round("123456"), Some("123000".to_string()));
+        assert_eq!(round("99.96"), Some("100".to_string()));
+        assert_eq!(round("1.2345E+10"), Some("1.23E+10".to_string()));
+        assert_eq!(round("1.23"), None);
+    }
+
+    #[test]
+    fn test_number_stats() {
+        let json = r#"{"coords": [[1.123456789, 2.5], [3.0000001, 4]], "id": 123456789}"#;
+        let js = JsonSize::new(json);
+        let mut stats = NumberStats::new(Precision::Decimals(3));
+        stats.add(&js, json);
+
+        let sorted = stats.sorted();
+        assert_eq!(sorted.len(), 2);
+        assert_eq!(
+            sorted[0],
+            (
+                "/coords/*/*",
+                &NumberPathStats {
+                    count: 4,
+                    bytes: 11 + 3 + 9 + 1,
+                    max_decimals: 9,
+                    max_significant: 10,
+                    imprecise: 2,
+                    unroundable: 0,
+                    savings: 6 + 8,
+                }
+            )
+        );
+        assert_eq!(sorted[1].1.imprecise, 0);
+
+        let json = "[1e99999999999999999999, 1.23456]";
+        let mut stats = NumberStats::new(Precision::Decimals(3));
+        stats.add(&JsonSize::new(json), json);
+        let sorted = stats.sorted();
+        assert_eq!(sorted[0].1.count, 2);
+        assert_eq!(sorted[0].1.imprecise, 1);
+        assert_eq!(sorted[0].1.unroundable, 1);
+        assert!(
+            stats.display_list(None)[2].contains("Total: 1 of 2 numbers (1 can not be rounded)")
+        );
+    }
+
+    #[test]
+    fn test_round_numbers() {
+        let json = "{\"a\": [1.23456, 2],\n \"b\": {\"c\": 0.1111}, \"d\": 9.87654}";
+        let js = JsonSize::new(json);
+        let nodes = [js.get_path(&[0]).unwrap(), js.get_path(&[1]).unwrap(), &js];
+        assert_eq!(
+            round_numbers(json, &nodes[..2], Precision::Decimals(1)),
+            "{\"a\": [1.2, 2],\n \"b\": {\"c\": 0.1}, \"d\": 9.87654}"
+        );
+        assert_eq!(
+            round_numbers(json, &nodes, Precision::Decimals(1)),
+            "{\"a\": [1.2, 2],\n \"b\": {\"c\": 0.1}, \"d\": 9.9}"
+        );
+    }
+}
@@ -0,0 +1,533 @@
+//! Classify the contents of string values: base64 blobs, data URIs, hex strings, UUIDs,
+//! timestamps, URLs and stringified JSON. Strings that contain JSON can be expanded and explored
+//! like any other subtree.
+use crate::size::{percent_of, size_bar, unescape_json_string, Dialect, JsonSize, JsonValueKind};
+use std::collections::HashMap;
+
+/// Shorter strings are not considered base64 or hex, too many words would match
+const MIN_ENCODED_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PayloadClass {
+    /// An object or array serialized as a string
+    Json,
+    DataUri,
+    Base64,
+    Hex,
+    Uuid,
+    Timestamp,
+    Url,
+    /// Anything else
+    Text,
+}
+
+/// Class of a string and the size of its contents once decoded, if it is encoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payload {
+    pub class: PayloadClass,
+    pub decoded_size: Option<usize>,
+    /// MIME type of data URIs
+    pub mime: Option<String>,
+}
+
+impl Payload {
+    /// Classify the raw contents of a string, without quotes
+    pub fn classify(raw: &str) -> Self {
+        let s = if raw.contains('\\') {
+            unescape_json_string(raw)
+        } else {
+            raw.to_string()
+        };
+        let payload = |class, decoded_size| Payload {
+            class,
+            decoded_size,
+            mime: None,
+        };
+
+        if let Some((header, data)) = s.strip_prefix("data:").and_then(|x| x.split_once(',')) {
+            let (mime, is_base64) = match header.strip_suffix(";base64") {
+                Some(mime) => (mime, true),
+                None => (header, false),
+            };
+            let mime = mime.split(';').next().unwrap();
+            return Payload {
+                class: PayloadClass::DataUri,
+                decoded_size: Some(if is_base64 {
+                    base64_decoded_len(data)
+                } else {
+                    data.len()
+                }),
+                mime: Some(if mime.is_empty() { "text/plain" } else { mime }.to_string()),
+            };
+        }
+        if is_json_container(&s) {
+            return payload(PayloadClass::Json, None);
+        }
+        if is_uuid(&s) {
+            return payload(PayloadClass::Uuid, None);
+        }
+        if is_timestamp(&s) {
+            return payload(PayloadClass::Timestamp, None);
+        }
+        if is_url(&s) {
+            return payload(PayloadClass::Url, None);
+        }
+        if s.len() >= MIN_ENCODED_LEN
+            && s.len() % 2 == 0
+            && s.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            return payload(PayloadClass::Hex, Some(s.len() / 2));
+        }
+        if is_base64(&s) {
+            return payload(PayloadClass::Base64, Some(base64_decoded_len(&s)));
+        }
+
+        payload(PayloadClass::Text, None)
+    }
+}
+
+fn is_json_container(s: &str) -> bool {
+    let s = s.trim();
+    let looks_like_json =
+        (s.starts_with('{') && s.ends_with('}')) || (s.starts_with('[') && s.ends_with(']'));
+    looks_like_json && JsonSize::try_new(s).is_some()
+}
+
+fn is_uuid(s: &str) -> bool {
+    s.len() == 36
+        && s.bytes().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
+}
+
+/// ISO 8601 date, optionally followed by a time and a time zone:
+/// "2024-01-31", "2024-01-31T12:00:00.123Z", "2024-01-31 12:00+02:00"
+fn is_timestamp(s: &str) -> bool {
+    let digits =
+        |s: &str, n: usize| s.len() >= n && s.as_bytes()[..n].iter().all(u8::is_ascii_digit);
+    let b = s.as_bytes();
+    let is_date = digits(s, 4)
+        && b.get(4) == Some(&b'-')
+        && digits(&s[5..], 2)
+        && b.get(7) == Some(&b'-')
+        && digits(&s[8..], 2);
+    if !is_date {
+        return false;
+    }
+    let mut rest = &s[10..];
+    if rest.is_empty() {
+        return true;
+    }
+    // Time
+    rest = match rest.strip_prefix(['T', ' ']) {
+        Some(x) if digits(x, 2) && x.as_bytes().get(2) == Some(&b':') && digits(&x[3..], 2) => {
+            &x[5..]
+        }
+        _ => return false,
+    };
+    if let Some(x) = rest.strip_prefix(':') {
+        if !digits(x, 2) {
+            return false;
+        }
+        rest = &x[2..];
+        if let Some(x) = rest.strip_prefix(['.', ',']) {
+            rest = x.trim_start_matches(|c: char| c.is_ascii_digit());
+        }
+    }
+    // Time zone
+    match rest.strip_prefix(['+', '-']) {
+        Some(x) => {
+            let x = x.replacen(':', "", 1);
+            x.len() == 4 && digits(&x, 4)
+        }
+        None => rest.is_empty() || rest == "Z",
+    }
+}
+
+fn is_url(s: &str) -> bool {
+    match s.split_once("://") {
+        Some((scheme, rest)) => {
+            !rest.is_empty()
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+                && !s.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+/// Standard or URL-safe base64, with or without padding. To avoid matching long words, it must
+/// contain uppercase and lowercase letters and at least one digit or symbol.
+fn is_base64(s: &str) -> bool {
+    let data = s.trim_end_matches('=');
+    if s.len() < MIN_ENCODED_LEN || s.len() - data.len() > 2 || data.len() % 4 == 1 {
+        return false;
+    }
+    if s.len() != data.len() && !s.len().is_multiple_of(4) {
+        return false;
+    }
+    let standard = data
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/');
+    let url_safe = data
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+
+    (standard || url_safe)
+        && data.bytes().any(|b| b.is_ascii_uppercase())
+        && data.bytes().any(|b| b.is_ascii_lowercase())
+        && data.bytes().any(|b| !b.is_ascii_alphabetic())
+}
+
+fn base64_decoded_len(s: &str) -> usize {
+    s.trim_end_matches('=').len() * 3 / 4
+}
+
+/// Decode standard or URL-safe base64, padding is optional
+pub fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let value = |b: u8| match b {
+        b'A'..=b'Z' => Some(b - b'A'),
+        b'a'..=b'z' => Some(b - b'a' + 26),
+        b'0'..=b'9' => Some(b - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    };
+    let data = s.trim_end_matches('=').as_bytes();
+    if data.len() % 4 == 1 {
+        return None;
+    }
+    let mut decoded = Vec::with_capacity(data.len() * 3 / 4);
+    for chunk in data.chunks(4) {
+        let mut bits = 0u32;
+        for (i, &b) in chunk.iter().enumerate() {
+            bits |= (value(b)? as u32) << (18 - 6 * i);
+        }
+        let bytes = bits.to_be_bytes();
+        decoded.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+
+    Some(decoded)
+}
+
+/// If the raw contents of a string (without quotes) are an object or array, directly or encoded
+/// as base64, return the decoded JSON
+pub fn decode_embedded_json(raw: &str) -> Option<String> {
+    let s = unescape_json_string(raw);
+    if is_json_container(&s) {
+        return Some(s);
+    }
+    if is_base64(&s) {
+        let decoded = String::from_utf8(decode_base64(&s)?).ok()?;
+        if is_json_container(&decoded) {
+            return Some(decoded);
+        }
+    }
+
+    None
+}
+
+/// Follow a JSON pointer, and when it reaches a string that contains JSON, continue inside the
+/// decoded string. If the pointer ends at a string that contains JSON, that string is decoded as
+/// well. Returns the innermost JSON document and the rest of the pointer relative to it.
+/// `dialect` only applies to the outer document, embedded documents must be JSON.
+pub fn resolve_embedded(json: String, pointer: &str, dialect: Dialect) -> Option<(String, String)> {
+    let mut json = json;
+    let mut dialect = dialect;
+    let mut tokens: Vec<&str> = pointer.split('/').skip(1).collect();
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        return None;
+    }
+    loop {
+        let js = JsonSize::try_with_dialect(&json, dialect)?;
+        // Longest prefix of the pointer that exists in this document
+        let mut path = vec![];
+        let mut n = 0;
+        while n < tokens.len() {
+            let prefix = format!("/{}", tokens[..=n].join("/"));
+            match js.json_pointer_path(&json, &prefix) {
+                Some(x) => path = x,
+                None => break,
+            }
+            n += 1;
+        }
+        let target = js.get_path(&path).unwrap();
+        let decoded = match target.value_kind() {
+            JsonValueKind::String => {
+                let span = target.span();
+                decode_embedded_json(&json[span.start + 1..span.end - 1])
+            }
+            _ => None,
+        };
+        match decoded {
+            Some(decoded) => {
+                json = decoded;
+                dialect = Dialect::Json;
+                tokens.drain(..n);
+            }
+            None if n == tokens.len() => {
+                let pointer = tokens[..n].iter().map(|x| format!("/{}", x)).collect();
+                return Some((json, pointer));
+            }
+            None => return None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ClassStats {
+    pub count: usize,
+    /// Bytes of the strings, including quotes
+    pub bytes: usize,
+    /// Size of the decoded contents, for the classes that are encoded
+    pub decoded_bytes: usize,
+}
+
+impl ClassStats {
+    fn add(&mut self, bytes: usize, payload: &Payload) {
+        self.count += 1;
+        self.bytes += bytes;
+        self.decoded_bytes += payload.decoded_size.unwrap_or(0);
+    }
+}
+
+/// Accumulates string statistics by class, by generalized path (see `schema`) and class, and by
+/// MIME type of data URIs. Can be fed more than one tree.
+#[derive(Debug, Default)]
+pub struct PayloadStats {
+    classes: HashMap<PayloadClass, ClassStats>,
+    paths: HashMap<(String, PayloadClass), ClassStats>,
+    mimes: HashMap<String, ClassStats>,
+    /// Sum of the sizes of all the trees
+    total: usize,
+}
+
+impl PayloadStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, js: &JsonSize, json: &str) {
+        self.total += js.total_size();
+        let mut path = String::new();
+        self.add_value(js, json, &mut path);
+    }
+
+    fn add_value(&mut self, js: &JsonSize, json: &str, path: &mut String) {
+        if js.value_kind() == JsonValueKind::String {
+            let span = js.span();
+            let payload = Payload::classify(&json[span.start + 1..span.end - 1]);
+            let bytes = span.len();
+            self.classes
+                .entry(payload.class)
+                .or_default()
+                .add(bytes, &payload);
+            self.paths
+                .entry((path.clone(), payload.class))
+                .or_default()
+                .add(bytes, &payload);
+            if let Some(mime) = &payload.mime {
+                self.mimes
+                    .entry(mime.clone())
+                    .or_default()
+                    .add(bytes, &payload);
+            }
+            return;
+        }
+
+        let path_len = path.len();
+        for child in js.children() {
+            path.push('/');
+            match js.value_kind() {
+                JsonValueKind::Array => path.push('*'),
+                _ => path.push_str(&child.key().to_json_pointer_token(json)),
+            }
+            self.add_value(child, json, path);
+            path.truncate(path_len);
+        }
+    }
+
+    /// All the classes that were found, most bytes first
+    pub fn sorted_classes(&self) -> Vec<(PayloadClass, &ClassStats)> {
+        let mut classes: Vec<_> = self.classes.iter().map(|(&k, v)| (k, v)).collect();
+        classes.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then_with(|| a.0.cmp(&b.0)));
+        classes
+    }
+
+    /// Generalized paths and class of their strings, most bytes first. Plain text is not
+    /// included.
+    pub fn sorted_paths(&self) -> Vec<(&str, PayloadClass, &ClassStats)> {
+        let mut paths: Vec<_> = self
+            .paths
+            .iter()
+            .filter(|((_, class), _)| *class != PayloadClass::Text)
+            .map(|((path, class), stats)| (path.as_str(), *class, stats))
+            .collect();
+        paths.sort_by(|a, b| {
+            b.2.bytes
+                .cmp(&a.2.bytes)
+                .then_with(|| a.0.cmp(b.0))
+                .then_with(|| a.1.cmp(&b.1))
+        });
+        paths
+    }
+
+    /// Format as tables by class, by MIME type and by path, showing at most `limit` paths
+    pub fn display_list(&self, limit: Option<usize>) -> Vec<String> {
+        let row = |name: &str, stats: &ClassStats| {
+            format!(
+                "{:12} {} {:10} {:12} {}",
+                stats.bytes,
+                size_bar(stats.bytes, self.total),
+                stats.count,
+                stats.decoded_bytes,
+                name
+            )
+        };
+        let header = |name: &str| {
+            format!(
+                "{:>12} {:12} {:>10} {:>12} {}",
+                "Bytes", "", "Count", "Decoded", name
+            )
+        };
+
+        let mut lines = vec![header("Class")];
+        let mut string_bytes = 0;
+        for (class, stats) in self.sorted_classes() {
+            string_bytes += stats.bytes;
+            lines.push(row(&format!("{:?}", class), stats));
+        }
+        lines.push(format!(
+            "{:12} {} Total: {:.1}% of {} bytes are strings",
+            string_bytes,
+            size_bar(string_bytes, self.total),
+            percent_of(string_bytes, self.total),
+            self.total
+        ));
+
+        if !self.mimes.is_empty() {
+            let mut mimes: Vec<_> = self.mimes.iter().collect();
+            mimes.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then_with(|| a.0.cmp(b.0)));
+            lines.push(String::new());
+            lines.push(header("MIME type"));
+            for (mime, stats) in mimes {
+                lines.push(row(mime, stats));
+            }
+        }
+
+        lines.push(String::new());
+        lines.push(header("Path"));
+        for (path, class, stats) in self
+            .sorted_paths()
+            .into_iter()
+            .take(limit.unwrap_or(usize::MAX))
+        {
+            lines.push(row(&format!("{} ({:?})", path, class), stats));
+        }
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(s: &str) -> PayloadClass {
+        Payload::classify(s).class
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(class(r#"{\"a\": [1, 2]}"#), PayloadClass::Json);
+        assert_eq!(class("[1, 2"), PayloadClass::Text);
+        assert_eq!(
+            class("123e4567-e89b-12d3-a456-426614174000"),
+            PayloadClass::Uuid
+        );
+        assert_eq!(class("2024-01-31"), PayloadClass::Timestamp);
+        assert_eq!(class("2024-01-31T12:00:00.123Z"), PayloadClass::Timestamp);
+        assert_eq!(class("2024-01-31 12:00+02:00"), PayloadClass::Timestamp);
+        assert_eq!(class("2024-01-31T12"), PayloadClass::Text);
+        assert_eq!(class("https://example.com/a?b=c"), PayloadClass::Url);
+        assert_eq!(class("see https://example.com"), PayloadClass::Text);
+        assert_eq!(class("d41d8cd98f00b204e9800998ecf8427e"), PayloadClass::Hex);
+        assert_eq!(class("SGVsbG8sIFdvcmxkIQ=="), PayloadClass::Base64);
+        assert_eq!(class("SGVsbG8sIFdvcmxkIQ"), PayloadClass::Base64);
+        assert_eq!(class("internationalization"), PayloadClass::Text);
+        assert_eq!(class("hello world"), PayloadClass::Text);
+    }
+
+    #[test]
+    fn test_data_uri() {
+        assert_eq!(
+            Payload::classify("data:image/png;base64,iVBORw0KGgo="),
+            Payload {
+                class: PayloadClass::DataUri,
+                decoded_size: Some(8),
+                mime: Some("image/png".to_string()),
+            }
+        );
+        assert_eq!(
+            Payload::classify("data:,Hello").mime,
+            Some("text/plain".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(
+            decode_base64("SGVsbG8sIFdvcmxkIQ=="),
+            Some(b"Hello, World!".to_vec())
+        );
+        assert_eq!(
+            decode_base64("SGVsbG8sIFdvcmxkIQ"),
+            Some(b"Hello, World!".to_vec())
+        );
+        assert_eq!(decode_base64("-_8"), Some(vec![0xfb, 0xff]));
+        assert_eq!(decode_base64("a"), None);
+        assert_eq!(decode_base64("a*=="), None);
+    }
+
+    #[test]
+    fn test_resolve_embedded() {
+        // {"b": [1, {"c": true}]} in base64
+        let json = r#"{"a": "{\"x\": \"eyJiIjogWzEsIHsiYyI6IHRydWV9XX0=\"}", "d": 1}"#;
+        assert_eq!(
+            resolve_embedded(json.to_string(), "/a/x/b/1", Dialect::Json),
+            Some((r#"{"b": [1, {"c": true}]}"#.to_string(), "/b/1".to_string()))
+        );
+        assert_eq!(
+            resolve_embedded(json.to_string(), "/a", Dialect::Json),
+            Some((
+                r#"{"x": "eyJiIjogWzEsIHsiYyI6IHRydWV9XX0="}"#.to_string(),
+                "".to_string()
+            ))
+        );
+        assert_eq!(
+            resolve_embedded(json.to_string(), "/d", Dialect::Json),
+            Some((json.to_string(), "/d".to_string()))
+        );
+        assert_eq!(
+            resolve_embedded(json.to_string(), "/a/y", Dialect::Json),
+            None
+        );
+    }
+
+    #[test]
+    fn test_payload_stats() {
+        let json = r#"[{"id": "123e4567-e89b-12d3-a456-426614174000", "n": "x"}, {"id": "x"}]"#;
+        let mut stats = PayloadStats::new();
+        stats.add(&JsonSize::new(json), json);
+
+        let classes = stats.sorted_classes();
+        assert_eq!(classes[0].0, PayloadClass::Uuid);
+        assert_eq!(classes[1].1.count, 2);
+        let paths = stats.sorted_paths();
+        assert_eq!(paths.len(), 1);
+        assert_eq!((paths[0].0, paths[0].1), ("/*/id", PayloadClass::Uuid));
+    }
+}
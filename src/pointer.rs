@@ -0,0 +1,361 @@
+//! JSON pointer utilities that go beyond RFC 6901 lookups:
+//! Relative JSON Pointers, and finding the pointer of the value at a given position in the file.
+use crate::size::{JsonSize, JsonValueKind};
+use std::str::FromStr;
+
+/// Relative JSON Pointer (draft-bhutton-relative-json-pointer-00), for example `1/name` or `0#`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelativeJsonPointer {
+    /// How many levels to go up from the current value
+    up: usize,
+    /// Added to the array index of the value after going up
+    index_offset: isize,
+    /// Either `#` or a JSON pointer to apply after going up
+    tail: RelativeTail,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RelativeTail {
+    KeyOrIndex,
+    Pointer(String),
+}
+
+/// Result of evaluating a relative JSON pointer
+#[derive(Debug, PartialEq, Eq)]
+pub enum RelativeTarget {
+    /// Path of child indices from the root to the referenced value
+    Value(Vec<usize>),
+    /// The referenced value is an object member and the pointer ended with `#`
+    Key(String),
+    /// The referenced value is an array item and the pointer ended with `#`
+    Index(usize),
+}
+
+impl FromStr for RelativeJsonPointer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let up = parse_non_negative_integer(&s[..digits])
+            .ok_or_else(|| format!("relative JSON pointer must start with a number: {:?}", s))?;
+        let mut rest = &s[digits..];
+
+        let mut index_offset = 0;
+        if rest.starts_with(['+', '-']) {
+            let digits = rest[1..]
+                .find(|c: char| !c.is_ascii_digit())
+                .map_or(rest.len(), |x| x + 1);
+            let offset = parse_non_negative_integer(&rest[1..digits])
+                .and_then(|x| isize::try_from(x).ok())
+                .ok_or_else(|| format!("invalid index manipulation: {:?}", s))?;
+            index_offset = if rest.starts_with('-') {
+                -offset
+            } else {
+                offset
+            };
+            rest = &rest[digits..];
+        }
+
+        let tail = match rest {
+            "#" => RelativeTail::KeyOrIndex,
+            "" => RelativeTail::Pointer(String::new()),
+            x if x.starts_with('/') => RelativeTail::Pointer(x.to_string()),
+            _ => return Err(format!("invalid relative JSON pointer: {:?}", s)),
+        };
+
+        Ok(Self {
+            up,
+            index_offset,
+            tail,
+        })
+    }
+}
+
+impl RelativeJsonPointer {
+    /// Evaluate the pointer starting from the value at `current`, a path of child indices from `root`.
+    pub fn resolve(
+        &self,
+        root: &JsonSize,
+        json: &str,
+        current: &[usize],
+    ) -> Option<RelativeTarget> {
+        let mut path = current.get(..current.len().checked_sub(self.up)?)?.to_vec();
+
+        if self.index_offset != 0 {
+            // Index manipulation is only allowed on array items
+            let (last, parent_path) = path.split_last_mut()?;
+            let parent = root.get_path(parent_path)?;
+            if parent.value_kind() != JsonValueKind::Array {
+                return None;
+            }
+            *last = last
+                .checked_add_signed(self.index_offset)
+                .filter(|&i| i < parent.children().len())?;
+        }
+
+        match &self.tail {
+            RelativeTail::KeyOrIndex => {
+                let (_, parent_path) = path.split_last()?;
+                let node = root.get_path(&path)?;
+                match root.get_path(parent_path)?.value_kind() {
+                    JsonValueKind::Object => Some(RelativeTarget::Key(
+                        crate::size::unescape_json_string(node.key().get_key_str(json)?),
+                    )),
+                    _ => Some(RelativeTarget::Index(node.key().index())),
+                }
+            }
+            RelativeTail::Pointer(pointer) => {
+                let node = root.get_path(&path)?;
+                path.extend(node.json_pointer_path(json, pointer)?);
+                Some(RelativeTarget::Value(path))
+            }
+        }
+    }
+}
+
+fn parse_non_negative_integer(s: &str) -> Option<usize> {
+    if s.is_empty() || (s.starts_with('0') && s.len() != 1) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// A position inside the JSON file, as reported by other tools
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Position {
+    /// Byte offset, starting at 0
+    Offset(usize),
+    /// Line and byte column, both starting at 1 (the convention used by serde_json errors)
+    LineColumn { line: usize, column: usize },
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected byte offset or line:column, got {:?}", s);
+        match s.split_once(':') {
+            Some((line, column)) => {
+                let line = line.parse().map_err(|_| invalid())?;
+                let column = column.parse().map_err(|_| invalid())?;
+                if line == 0 || column == 0 {
+                    return Err(invalid());
+                }
+                Ok(Position::LineColumn { line, column })
+            }
+            None => s.parse().map(Position::Offset).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl Position {
+    /// Convert to a byte offset. Returns `None` if the position is outside of `json`.
+    pub fn to_offset(self, json: &str) -> Option<usize> {
+        match self {
+            Position::Offset(offset) => Some(offset).filter(|&x| x < json.len()),
+            Position::LineColumn { line, column } => {
+                let line_start = if line == 1 {
+                    0
+                } else {
+                    json.match_indices('\n').nth(line - 2)?.0 + 1
+                };
+                let line_end = json[line_start..]
+                    .find('\n')
+                    .map_or(json.len(), |x| line_start + x + 1);
+                line_start.checked_add(column - 1).filter(|&x| x < line_end)
+            }
+        }
+    }
+}
+
+/// The innermost value that contains a given byte offset
+#[derive(Debug, PartialEq, Eq)]
+pub struct Location {
+    /// Path of child indices from the root to the value
+    pub path: Vec<usize>,
+    /// The offset is inside the key of the value, not the value itself
+    pub in_key: bool,
+}
+
+/// Find the innermost value whose text contains `offset`.
+/// Offsets in whitespace, commas or colons belong to the enclosing container.
+pub fn locate_offset(root: &JsonSize, offset: usize) -> Location {
+    let mut path = vec![];
+    let mut target = root;
+
+    loop {
+        let children = target.children();
+        // Children are sorted by position, find the last one that starts before offset
+        let i = children.partition_point(|child| member_start(child) <= offset);
+        let child = match i.checked_sub(1) {
+            Some(i) => &children[i],
+            None => break,
+        };
+        if child.span().contains(&offset) {
+            path.push(i - 1);
+            target = child;
+            continue;
+        }
+        if let Some(key_span) = child.key_text_span() {
+            if key_span.contains(&offset) {
+                path.push(i - 1);
+                return Location { path, in_key: true };
+            }
+        }
+        break;
+    }
+
+    Location {
+        path,
+        in_key: false,
+    }
+}
+
+fn member_start(js: &JsonSize) -> usize {
+    match js.key_text_span() {
+        Some(key_span) => key_span.start,
+        None => js.span().start,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(json: &str, current: &str, relative: &str) -> Option<RelativeTarget> {
+        let js = JsonSize::new(json);
+        let current = js.json_pointer_path(json, current).unwrap();
+        let relative: RelativeJsonPointer = relative.parse().unwrap();
+        relative.resolve(&js, json, &current)
+    }
+
+    fn resolve_pointer(json: &str, current: &str, relative: &str) -> Option<String> {
+        let js = JsonSize::new(json);
+        match resolve(json, current, relative)? {
+            RelativeTarget::Value(path) => js.path_to_json_pointer(json, &path),
+            x => panic!("expected value, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_parse_relative_pointer() {
+        assert!("0".parse::<RelativeJsonPointer>().is_ok());
+        assert!("1/a/b".parse::<RelativeJsonPointer>().is_ok());
+        assert!("2#".parse::<RelativeJsonPointer>().is_ok());
+        assert!("0-1#".parse::<RelativeJsonPointer>().is_ok());
+        assert!("0+10/x".parse::<RelativeJsonPointer>().is_ok());
+        assert!("".parse::<RelativeJsonPointer>().is_err());
+        assert!("/a".parse::<RelativeJsonPointer>().is_err());
+        assert!("01".parse::<RelativeJsonPointer>().is_err());
+        assert!("1a".parse::<RelativeJsonPointer>().is_err());
+        assert!("0+".parse::<RelativeJsonPointer>().is_err());
+    }
+
+    #[test]
+    fn test_relative_pointer_spec_examples() {
+        // Examples from section 5.1 of the draft
+        let json = r#"{"foo": ["bar", "baz"], "highly": {"nested": {"objects": true}}}"#;
+        assert_eq!(resolve_pointer(json, "/foo/1", "0").unwrap(), "/foo/1");
+        assert_eq!(resolve_pointer(json, "/foo/1", "1/0").unwrap(), "/foo/0");
+        assert_eq!(resolve_pointer(json, "/foo/1", "0-1").unwrap(), "/foo/0");
+        assert_eq!(
+            resolve_pointer(json, "/foo/1", "2/highly/nested/objects").unwrap(),
+            "/highly/nested/objects"
+        );
+        assert_eq!(
+            resolve(json, "/foo/1", "0#"),
+            Some(RelativeTarget::Index(1))
+        );
+        assert_eq!(
+            resolve(json, "/foo/1", "1#"),
+            Some(RelativeTarget::Key("foo".to_string()))
+        );
+        assert_eq!(
+            resolve_pointer(json, "/highly/nested", "0/objects").unwrap(),
+            "/highly/nested/objects"
+        );
+        assert_eq!(
+            resolve(json, "/highly/nested", "0#"),
+            Some(RelativeTarget::Key("nested".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_relative_pointer_errors() {
+        let json = r#"{"foo": ["bar", "baz"]}"#;
+        // Above the root
+        assert_eq!(resolve(json, "/foo/1", "3"), None);
+        // Root has no key
+        assert_eq!(resolve(json, "/foo/1", "2#"), None);
+        // Index out of bounds
+        assert_eq!(resolve(json, "/foo/1", "0+1"), None);
+        // Index manipulation on an object member
+        assert_eq!(resolve(json, "/foo/1", "1+1"), None);
+    }
+
+    #[test]
+    fn test_position_to_offset() {
+        let json = "{\n  \"a\": 1\n}";
+        assert_eq!("4".parse::<Position>().unwrap().to_offset(json), Some(4));
+        assert_eq!("1:1".parse::<Position>().unwrap().to_offset(json), Some(0));
+        assert_eq!("2:3".parse::<Position>().unwrap().to_offset(json), Some(4));
+        assert_eq!("3:1".parse::<Position>().unwrap().to_offset(json), Some(11));
+        assert_eq!("3:2".parse::<Position>().unwrap().to_offset(json), None);
+        assert_eq!("4:1".parse::<Position>().unwrap().to_offset(json), None);
+        let huge = format!("2:{}", usize::MAX);
+        assert_eq!(huge.parse::<Position>().unwrap().to_offset(json), None);
+        assert!("0:1".parse::<Position>().is_err());
+    }
+
+    #[test]
+    fn test_locate_offset() {
+        let json = r#"{"a": [10, {"b~/": "xyz"}], "c": null}"#;
+        let js = JsonSize::new(json);
+        let pointer = |offset| {
+            let location = locate_offset(&js, offset);
+            (
+                js.path_to_json_pointer(json, &location.path).unwrap(),
+                location.in_key,
+            )
+        };
+        // {
+        assert_eq!(pointer(0), ("".to_string(), false));
+        // "a"
+        assert_eq!(pointer(2), ("/a".to_string(), true));
+        // [
+        assert_eq!(pointer(6), ("/a".to_string(), false));
+        // 10
+        assert_eq!(pointer(8), ("/a/0".to_string(), false));
+        // ,
+        assert_eq!(pointer(9), ("/a".to_string(), false));
+        // "xyz"
+        assert_eq!(pointer(21), ("/a/1/b~0~1".to_string(), false));
+        // null
+        assert_eq!(pointer(35), ("/c".to_string(), false));
+    }
+
+    #[test]
+    fn test_locate_offset_json5() {
+        let json = "{abc: 1, 'd': 2}";
+        let js = JsonSize::with_dialect(json, crate::size::Dialect::Json5);
+        let pointer = |offset| {
+            let location = locate_offset(&js, offset);
+            (
+                js.path_to_json_pointer(json, &location.path).unwrap(),
+                location.in_key,
+            )
+        };
+        // {
+        assert_eq!(pointer(0), ("".to_string(), false));
+        // abc
+        assert_eq!(pointer(1), ("/abc".to_string(), true));
+        assert_eq!(pointer(3), ("/abc".to_string(), true));
+        // :
+        assert_eq!(pointer(4), ("".to_string(), false));
+        // 1
+        assert_eq!(pointer(6), ("/abc".to_string(), false));
+        // 'd'
+        assert_eq!(pointer(9), ("/d".to_string(), true));
+        assert_eq!(pointer(11), ("/d".to_string(), true));
+    }
+}
@@ -1,9 +1,130 @@
-use std::collections::VecDeque;
+use crate::size::Dialect;
 
 pub fn prettify(json: &str, indent_width: usize) -> String {
     PrettifyIter::new(json, indent_width).collect()
 }
 
+/// Prettify a JSONC or JSON5 file, keeping its comments. Comments that follow a value on the
+/// same line stay there, and comments on their own line stay on their own line.
+pub fn prettify_dialect(json: &str, indent_width: usize, dialect: Dialect) -> String {
+    if dialect == Dialect::Json {
+        return prettify(json, indent_width);
+    }
+
+    let mut out = String::with_capacity(json.len() * 2);
+    let mut chars = json.chars().peekable();
+    let mut depth = 0;
+    // Start the next token on a new line
+    let mut pending_newline = false;
+    // The previous token was `{` or `[`, so a closing bracket can go on the same line
+    let mut after_open = false;
+    // There was a newline between the previous token and the current one
+    let mut saw_newline = true;
+    // A block comment was followed by more tokens on the same line
+    let mut space_before_next = false;
+
+    let newline = |out: &mut String, depth: usize| {
+        let len = out.trim_end_matches(' ').len();
+        out.truncate(len);
+        if !out.is_empty() {
+            out.push('\n');
+            out.push_str(&" ".repeat(depth * indent_width));
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                saw_newline = true;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '/' if matches!(chars.peek(), Some('/' | '*')) => {
+                let line_comment = chars.peek() == Some(&'/');
+                if saw_newline || out.is_empty() {
+                    newline(&mut out, depth);
+                    pending_newline = true;
+                } else if !out.ends_with(' ') {
+                    out.push(' ');
+                }
+                out.push(c);
+                out.push(chars.next().unwrap());
+                let mut prev = ' ';
+                while let Some(&c) = chars.peek() {
+                    if line_comment && c == '\n' {
+                        break;
+                    }
+                    chars.next();
+                    out.push(c);
+                    if !line_comment && prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                if line_comment {
+                    pending_newline = true;
+                } else {
+                    space_before_next = !pending_newline;
+                }
+                after_open = false;
+                saw_newline = false;
+                continue;
+            }
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                if !after_open {
+                    newline(&mut out, depth);
+                }
+                out.push(c);
+                pending_newline = false;
+                space_before_next = false;
+                after_open = false;
+            }
+            _ => {
+                if pending_newline {
+                    newline(&mut out, depth);
+                    pending_newline = false;
+                } else if space_before_next && c != ',' && c != ':' {
+                    out.push(' ');
+                }
+                space_before_next = false;
+                after_open = false;
+                match c {
+                    '{' | '[' => {
+                        out.push(c);
+                        depth += 1;
+                        pending_newline = true;
+                        after_open = true;
+                    }
+                    ',' => {
+                        out.push(c);
+                        pending_newline = true;
+                    }
+                    ':' => out.push_str(": "),
+                    '"' | '\'' => {
+                        out.push(c);
+                        let mut escape_next = false;
+                        for x in chars.by_ref() {
+                            out.push(x);
+                            if escape_next {
+                                escape_next = false;
+                            } else if x == '\\' {
+                                escape_next = true;
+                            } else if x == c {
+                                break;
+                            }
+                        }
+                    }
+                    _ => out.push(c),
+                }
+            }
+        }
+        saw_newline = false;
+    }
+
+    out
+}
+
 pub struct PrettifyIter<'a> {
     chars: std::str::Chars<'a>,
     buffer_0: Option<BufferChar>,
@@ -233,4 +354,23 @@ mod tests {
 ]"#
         );
     }
+
+    #[test]
+    fn it_keeps_comments_in_place() {
+        let json = "// config\n{a: 1, // one\n/* two */ 'b': [], c: {\n// nothing\n},}";
+        let prettified = prettify_dialect(json, 2, Dialect::Json5);
+
+        assert_eq!(
+            prettified,
+            r#"// config
+{
+  a: 1, // one
+  /* two */
+  'b': [],
+  c: {
+    // nothing
+  },
+}"#
+        );
+    }
 }
@@ -0,0 +1,169 @@
+//! Aggregate sizes over generalized paths, where array indices are replaced by `*`.
+//! For example the sizes of `/users/0/avatar` and `/users/1/avatar` are added to `/users/*/avatar`.
+use crate::size::{size_bar, Accounting, JsonSize, JsonValueKind};
+use std::collections::HashMap;
+
+/// Size statistics of all the values that share the same generalized path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathStats {
+    pub total: usize,
+    pub count: usize,
+    pub min: usize,
+    pub max: usize,
+}
+
+impl PathStats {
+    fn new(size: usize) -> Self {
+        Self {
+            total: size,
+            count: 1,
+            min: size,
+            max: size,
+        }
+    }
+
+    fn add(&mut self, size: usize) {
+        self.total += size;
+        self.count += 1;
+        self.min = self.min.min(size);
+        self.max = self.max.max(size);
+    }
+
+    pub fn avg(&self) -> usize {
+        self.total / self.count
+    }
+}
+
+/// Accumulates `PathStats` for every generalized path. Can be fed more than one tree, the paths
+/// are always relative to the tree passed to `add`.
+#[derive(Debug)]
+pub struct SchemaStats {
+    accounting: Accounting,
+    paths: HashMap<String, PathStats>,
+    /// Sum of the sizes of all the trees
+    total: usize,
+}
+
+impl SchemaStats {
+    pub fn new(accounting: Accounting) -> Self {
+        Self {
+            accounting,
+            paths: HashMap::new(),
+            total: 0,
+        }
+    }
+
+    pub fn add(&mut self, js: &JsonSize, json: &str) {
+        self.total += js.total_size();
+        let mut path = String::new();
+        self.add_children(js, json, &mut path);
+    }
+
+    fn add_children(&mut self, js: &JsonSize, json: &str, path: &mut String) {
+        let path_len = path.len();
+        for child in js.children() {
+            path.push('/');
+            match js.value_kind() {
+                JsonValueKind::Array => path.push('*'),
+                _ => path.push_str(&child.key().to_json_pointer_token(json)),
+            }
+
+            let size = child.size(self.accounting);
+            if let Some(stats) = self.paths.get_mut(path.as_str()) {
+                stats.add(size);
+            } else {
+                self.paths.insert(path.clone(), PathStats::new(size));
+            }
+            self.add_children(child, json, path);
+
+            path.truncate(path_len);
+        }
+    }
+
+    /// All the generalized paths, biggest total size first
+    pub fn sorted(&self) -> Vec<(&str, &PathStats)> {
+        let mut paths: Vec<_> = self
+            .paths
+            .iter()
+            .map(|(path, stats)| (path.as_str(), stats))
+            .collect();
+        paths.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(b.0)));
+        paths
+    }
+
+    /// Format as a table, showing at most `limit` paths
+    pub fn display_list(&self, limit: Option<usize>) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{:>12} {:12} {:>10} {:>10} {:>10} {:>10} Path",
+            "Total", "", "Count", "Min", "Avg", "Max"
+        )];
+        for (path, stats) in self.sorted().into_iter().take(limit.unwrap_or(usize::MAX)) {
+            lines.push(format!(
+                "{:12} {} {:10} {:10} {:10} {:10} {}",
+                stats.total,
+                size_bar(stats.total, self.total),
+                stats.count,
+                stats.min,
+                stats.avg(),
+                stats.max,
+                path
+            ));
+        }
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_array_indices_are_collapsed() {
+        let json = r#"{"users": [{"id": 1, "avatar": "xx"}, {"id": 22, "avatar": "xxxxxx"}]}"#;
+        let js = JsonSize::new(json);
+        let mut schema = SchemaStats::new(Accounting::Value);
+        schema.add(&js, json);
+
+        let paths: HashMap<_, _> = schema.sorted().into_iter().collect();
+        assert_eq!(paths.len(), 4);
+        assert_eq!(
+            paths["/users/*/avatar"],
+            &PathStats {
+                total: 5 + 9,
+                count: 2,
+                min: 5,
+                max: 9,
+            }
+        );
+        assert_eq!(paths["/users/*/id"].total, 2 + 3);
+        assert_eq!(paths["/users/*"].count, 2);
+        assert_eq!(paths["/users"].total, js.total_size() - 2 - 7 - 1);
+        assert_eq!(schema.sorted()[0].0, "/users");
+    }
+
+    #[test]
+    fn test_multiple_trees() {
+        let mut schema = SchemaStats::new(Accounting::Value);
+        for json in [r#"{"a/b": 1}"#, r#"{"a/b": 100, "c": []}"#] {
+            schema.add(&JsonSize::new(json), json);
+        }
+
+        let paths: HashMap<_, _> = schema.sorted().into_iter().collect();
+        assert_eq!(paths["/a~1b"].count, 2);
+        assert_eq!(paths["/a~1b"].avg(), 3);
+        assert_eq!(paths["/c"].count, 1);
+    }
+
+    #[test]
+    fn test_member_accounting() {
+        let json = r#"{"a": [1, 2], "b": 3}"#;
+        let mut schema = SchemaStats::new(Accounting::Member);
+        schema.add(&JsonSize::new(json), json);
+
+        let paths: HashMap<_, _> = schema.sorted().into_iter().collect();
+        assert_eq!(paths["/a"].total, r#""a": [1, 2],"#.len());
+        assert_eq!(paths["/a/*"].total, "1, 2".len());
+        assert_eq!(paths["/b"].total, r#" "b": 3"#.len());
+    }
+}
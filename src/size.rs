@@ -1,11 +1,20 @@
 //! Given a json file, calculate the size of each item.
 //! Display the data in a format similar to ncdu.
+use crate::compress::Compressor;
+use crate::encoding::Encoding;
+use crate::entropy::Entropy;
+use crate::memory;
+use crate::minify::minify_dialect;
+use std::borrow::Cow;
 use std::iter::Peekable;
+use std::ops::Range;
 
 #[derive(Default, Debug, PartialEq, Eq)]
 pub struct JsonSize {
     /// Size that will disappear after minifying the json file
     whitespace: usize,
+    /// Line and block comments, only allowed by some dialects. Also disappear after minifying.
+    comments: usize,
     /// Commas, quotes, and other JSON characters
     control_chars: usize,
     /// Actual data: strings, numbers, and keywords
@@ -14,63 +23,668 @@ pub struct JsonSize {
     children: Vec<JsonSize>,
     value_kind: JsonValueKind,
     key: JsonKey,
+    /// Bytes of the array item or object member that are not part of the value
+    member: MemberOverhead,
+    /// Encoding of the input file, all the sizes are in bytes of this encoding
+    encoding: Encoding,
+    /// Only set when the sizes were measured in a source format that is not JSON, like YAML.
+    /// Otherwise the kind of each control character follows from the JSON structure.
+    source_control: Option<SourceControl>,
+}
+
+/// The key, colon and trailing comma of an object member, and the whitespace around them.
+/// Array items only have the trailing comma. The whitespace around the value itself is already
+/// part of the value.
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+struct MemberOverhead {
+    whitespace: usize,
+    comments: usize,
+    control_chars: usize,
+    /// Quotes of the key, part of `control_chars`. 0 for unquoted JSON5 keys.
+    quotes: usize,
+    data_size: usize,
+}
+
+/// Control characters by kind, measured in a source file that is not JSON
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+struct SourceControl {
+    /// Of this value, not inside a child
+    own: ControlChars,
+    member: ControlChars,
+}
+
+impl MemberOverhead {
+    fn total_size(&self) -> usize {
+        self.whitespace + self.comments + self.control_chars + self.data_size
+    }
+}
+
+/// Extensions of JSON accepted by the parser
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Dialect {
+    /// Strict JSON (RFC 8259)
+    #[default]
+    Json,
+    /// JSON with `//` and `/* */` comments and trailing commas
+    Jsonc,
+    /// JSONC plus unquoted keys, single quoted strings, hexadecimal numbers, leading or trailing
+    /// decimal points, leading plus signs, `Infinity` and `NaN`
+    Json5,
+}
+
+impl Dialect {
+    /// Dialect implied by a file extension: `.jsonc` or `.json5`, also when compressed like
+    /// `.json5.gz`
+    pub fn from_extension(path: &std::path::Path) -> Option<Self> {
+        match crate::files::extension_without_compression(&path.to_string_lossy())? {
+            "jsonc" => Some(Dialect::Jsonc),
+            "json5" => Some(Dialect::Json5),
+            _ => None,
+        }
+    }
+
+    pub fn allows_comments(self) -> bool {
+        self != Dialect::Json
+    }
+
+    pub fn allows_trailing_commas(self) -> bool {
+        self != Dialect::Json
+    }
+}
+
+impl std::str::FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Dialect::Json),
+            "jsonc" => Ok(Dialect::Jsonc),
+            "json5" => Ok(Dialect::Json5),
+            _ => Err(format!(
+                "expected \"json\", \"jsonc\" or \"json5\", got {:?}",
+                s
+            )),
+        }
+    }
+}
+
+/// How to count the size of an array item or object member
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Accounting {
+    /// Include the key, colon and trailing comma: the bytes saved by deleting the member.
+    /// The children of a container add up to the size of the container minus its brackets.
+    #[default]
+    Member,
+    /// Only the value
+    Value,
+}
+
+impl std::str::FromStr for Accounting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "member" => Ok(Accounting::Member),
+            "value" => Ok(Accounting::Value),
+            _ => Err(format!("expected \"member\" or \"value\", got {:?}", s)),
+        }
+    }
+}
+
+/// Options for `JsonSize::display_list`
+#[derive(Default, Debug, Clone)]
+pub struct DisplayOptions {
+    pub accounting: Accounting,
+    /// Add columns with whitespace, control characters and data
+    pub breakdown: bool,
+    /// Add columns with the compressed size of the minified value of each item
+    pub compressors: Vec<Compressor>,
+    /// Add a column with the entropy of the data of each item
+    pub entropy: bool,
+    /// Add a column with the estimated memory used by each item once deserialized
+    pub memory: bool,
+    pub sort: SortBy,
+    /// Dialect of the input, used to strip comments before compressing
+    pub dialect: Dialect,
+}
+
+/// Order of the items in `JsonSize::display_list`
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SortBy {
+    /// Same order as in the file
+    #[default]
+    None,
+    /// Biggest first
+    Size,
+    /// Highest entropy first, implies showing the entropy column
+    Entropy,
+}
+
+impl std::str::FromStr for SortBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(SortBy::None),
+            "size" => Ok(SortBy::Size),
+            "entropy" => Ok(SortBy::Entropy),
+            _ => Err(format!(
+                "expected \"none\", \"size\" or \"entropy\", got {:?}",
+                s
+            )),
+        }
+    }
+}
+
+/// Control characters split by kind
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ControlChars {
+    pub quotes: usize,
+    pub commas: usize,
+    pub colons: usize,
+    /// Both `[]` and `{}`
+    pub brackets: usize,
+    /// YAML indicators that are none of the above: anchors, aliases, tags, block scalar headers
+    /// and document markers. Not a column of the breakdown, only counted in the total.
+    pub other: usize,
+}
+
+impl ControlChars {
+    pub fn total(&self) -> usize {
+        self.quotes + self.commas + self.colons + self.brackets + self.other
+    }
+
+    fn add(&mut self, other: &ControlChars) {
+        self.quotes += other.quotes;
+        self.commas += other.commas;
+        self.colons += other.colons;
+        self.brackets += other.brackets;
+        self.other += other.other;
+    }
+
+    fn sub(&mut self, other: &ControlChars) {
+        self.quotes -= other.quotes;
+        self.commas -= other.commas;
+        self.colons -= other.colons;
+        self.brackets -= other.brackets;
+        self.other -= other.other;
+    }
+}
+
+/// Bytes of a region of a source file that is not JSON, by kind
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct ByteCounts {
+    pub(crate) whitespace: usize,
+    pub(crate) comments: usize,
+    pub(crate) control: ControlChars,
+    pub(crate) data_size: usize,
+}
+
+impl ByteCounts {
+    pub(crate) fn add(&mut self, other: &ByteCounts) {
+        self.whitespace += other.whitespace;
+        self.comments += other.comments;
+        self.control.add(&other.control);
+        self.data_size += other.data_size;
+    }
+}
+
+/// Sizes of a value measured in a source file that was converted to JSON, like YAML. Has the
+/// same tree structure as the `JsonSize` of the converted JSON.
+#[derive(Default, Debug, PartialEq, Eq)]
+pub(crate) struct SourceSize {
+    /// The whole value, including its children
+    pub(crate) value: ByteCounts,
+    /// The key, indicators and whitespace of the item or member that are not part of the value
+    pub(crate) member: ByteCounts,
+    pub(crate) children: Vec<SourceSize>,
+    /// Byte range of the member in the source file, from its key or indicator to the next one
+    pub(crate) member_span: Range<usize>,
+    /// Byte range of the value in the source file
+    pub(crate) value_span: Range<usize>,
+}
+
+/// Size of a value split into whitespace, control characters and data
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SizeBreakdown {
+    pub whitespace: usize,
+    pub comments: usize,
+    pub control: ControlChars,
+    pub data_size: usize,
+}
+
+impl SizeBreakdown {
+    pub fn total_size(&self) -> usize {
+        self.whitespace + self.comments + self.control.total() + self.data_size
+    }
+
+    fn header() -> String {
+        format!(
+            "{:>10} {:>6} {:>10} {:>10} {:>8} {:>8} {:>8} {:>8} {:>10}",
+            "Whitespace",
+            "WS%",
+            "Comments",
+            "Control",
+            "Quotes",
+            "Commas",
+            "Colons",
+            "Brackets",
+            "Data"
+        )
+    }
+
+    fn to_display(self) -> String {
+        format!(
+            "{:10} {:5.1}% {:10} {:10} {:8} {:8} {:8} {:8} {:10}",
+            self.whitespace,
+            percent_of(self.whitespace, self.total_size()),
+            self.comments,
+            self.control.total(),
+            self.control.quotes,
+            self.control.commas,
+            self.control.colons,
+            self.control.brackets,
+            self.data_size
+        )
+    }
 }
 
 impl JsonSize {
+    /// Panics if the input is not valid JSON
     pub fn new(json: &str) -> Self {
-        // Assumes valid json
+        Self::with_dialect(json, Dialect::Json)
+    }
+
+    /// Panics if the input is not valid in this dialect
+    pub(crate) fn with_dialect(json: &str, dialect: Dialect) -> Self {
         let mut chars = json.char_indices().peekable();
-        let js = parse_json_size(&mut chars, 0).0;
+        let js = parse_json_size(&mut chars, 0, dialect)
+            .expect("Invalid json")
+            .0;
 
-        // Invariant: whitespace + control_chars + data_size == input.len()
+        // Invariant: whitespace + comments + control_chars + data_size == input.len()
         assert_total_size_invariant(json, &js);
 
         js
     }
 
+    /// Sizes in bytes of the original encoding of `json`, which must already be decoded to UTF-8.
+    /// The byte order mark counts as whitespace of the root. Spans are still byte ranges of
+    /// `json`. Returns `None` if the input is not valid in this dialect or if it is empty.
+    pub fn try_with_encoding(
+        json: &str,
+        dialect: Dialect,
+        encoding: Encoding,
+        bom: bool,
+    ) -> Option<Self> {
+        let mut js = Self::try_with_dialect(json, dialect)?;
+        if encoding != Encoding::Utf8 || bom {
+            // The same text in code units, so it is valid too
+            let code_units = Self::with_dialect(&encoding.code_unit_text(json), dialect);
+            js.copy_sizes(&code_units, encoding);
+            if bom {
+                js.whitespace += encoding.bom().len();
+            }
+        }
+
+        Some(js)
+    }
+
+    /// Sizes measured in a source file that was converted to `json`, like YAML. Spans are still
+    /// byte ranges of `json`. Panics if `json` is not valid JSON.
+    pub(crate) fn with_source_sizes(json: &str, sizes: &SourceSize) -> Self {
+        let mut js = Self::new(json);
+        js.copy_source_sizes(sizes);
+
+        js
+    }
+
+    fn copy_source_sizes(&mut self, other: &SourceSize) {
+        self.whitespace = other.value.whitespace;
+        self.comments = other.value.comments;
+        self.control_chars = other.value.control.total();
+        self.data_size = other.value.data_size;
+        self.member = MemberOverhead {
+            whitespace: other.member.whitespace,
+            comments: other.member.comments,
+            control_chars: other.member.control.total(),
+            quotes: other.member.control.quotes,
+            data_size: other.member.data_size,
+        };
+        let mut own_control = other.value.control;
+        for child in other.children.iter() {
+            own_control.sub(&child.value.control);
+        }
+        self.source_control = Some(SourceControl {
+            own: own_control,
+            member: other.member.control,
+        });
+        for (child, other) in self.children.iter_mut().zip(other.children.iter()) {
+            child.copy_source_sizes(other);
+        }
+    }
+
+    /// Take the sizes from a tree with the same structure, measured in code units
+    fn copy_sizes(&mut self, other: &JsonSize, encoding: Encoding) {
+        let unit = encoding.unit();
+        self.whitespace = other.whitespace * unit;
+        self.comments = other.comments * unit;
+        self.control_chars = other.control_chars * unit;
+        self.data_size = other.data_size * unit;
+        self.member = MemberOverhead {
+            whitespace: other.member.whitespace * unit,
+            comments: other.member.comments * unit,
+            control_chars: other.member.control_chars * unit,
+            quotes: other.member.quotes * unit,
+            data_size: other.member.data_size * unit,
+        };
+        self.encoding = encoding;
+        for (child, other) in self.children.iter_mut().zip(other.children.iter()) {
+            child.copy_sizes(other, encoding);
+        }
+    }
+
+    /// Returns `None` if the input is not valid JSON or if it is empty
+    pub fn try_new(json: &str) -> Option<Self> {
+        Self::try_with_dialect(json, Dialect::Json)
+    }
+
+    /// Returns `None` if the input is not valid in this dialect or if it is empty
+    pub fn try_with_dialect(json: &str, dialect: Dialect) -> Option<Self> {
+        let mut chars = json.char_indices().peekable();
+        match parse_json_size(&mut chars, 0, dialect) {
+            Ok((js, _, false)) => Some(js),
+            _ => None,
+        }
+    }
+
     fn add_stats_from(&mut self, other: &JsonSize) {
         self.whitespace += other.whitespace;
+        self.comments += other.comments;
         self.control_chars += other.control_chars;
         self.data_size += other.data_size;
     }
 
-    fn total_size(&self) -> usize {
-        self.whitespace + self.control_chars + self.data_size
+    pub fn total_size(&self) -> usize {
+        self.whitespace + self.comments + self.control_chars + self.data_size
+    }
+
+    /// Size after removing all the whitespace and comments
+    pub fn minified_size(&self) -> usize {
+        self.control_chars + self.data_size
+    }
+
+    /// Size of the value together with its key, colon and trailing comma
+    pub fn member_size(&self) -> usize {
+        self.total_size() + self.member.total_size()
+    }
+
+    /// Size of the key of an object member with its quotes, colon and the whitespace around
+    /// them, 0 for array items and the root
+    pub fn key_size(&self) -> usize {
+        if self.key.key_ptr.is_none() {
+            return 0;
+        }
+        let (quotes, colon) = match self.source_control {
+            Some(source_control) => (source_control.member.quotes, source_control.member.colons),
+            None => (self.member.quotes, self.encoding.unit()),
+        };
+
+        self.member.whitespace + quotes + self.member.data_size + colon
+    }
+
+    pub fn size(&self, accounting: Accounting) -> usize {
+        match accounting {
+            Accounting::Member => self.member_size(),
+            Accounting::Value => self.total_size(),
+        }
+    }
+
+    pub fn children(&self) -> &[JsonSize] {
+        &self.children
     }
 
-    pub fn display_list(&self, json: &str) -> Vec<String> {
-        if self.children.is_empty() {
-            let percent = "[##########]";
-            //let name = self.key.to_display(json);
-            let name = "Total";
-            let line = format!("{:12} {} {}", self.total_size(), percent, name);
-            vec![line]
+    pub fn value_kind(&self) -> JsonValueKind {
+        self.value_kind
+    }
+
+    pub fn key(&self) -> &JsonKey {
+        &self.key
+    }
+
+    /// Byte range of the key in the JSON input, with its quotes if it has them. `None` for array
+    /// items.
+    pub fn key_text_span(&self) -> Option<Range<usize>> {
+        let span = self.key.key_span()?;
+        // JSON converted from another format always quotes keys
+        let quote = usize::from(self.member.quotes > 0 || self.source_control.is_some());
+        Some(span.start - quote..span.end + quote)
+    }
+
+    /// Byte range of this value in the JSON input, without surrounding whitespace
+    pub fn span(&self) -> Range<usize> {
+        self.data_ptr.start..self.data_ptr.end
+    }
+
+    /// Follow a path of child indices, as returned by `json_pointer_path`
+    pub fn get_path(&self, path: &[usize]) -> Option<&Self> {
+        path.iter()
+            .try_fold(self, |target, &i| target.children.get(i))
+    }
+
+    /// Resolve a JSON pointer into the list of child indices that lead to the target
+    pub fn json_pointer_path(&self, json: &str, pointer: &str) -> Option<Vec<usize>> {
+        if pointer.is_empty() {
+            return Some(vec![]);
+        }
+        if !pointer.starts_with('/') {
+            return None;
+        }
+
+        let mut path = vec![];
+        let mut target = self;
+        for token in pointer
+            .split('/')
+            .skip(1)
+            .map(|x| x.replace("~1", "/").replace("~0", "~"))
+        {
+            let i = match target.value_kind {
+                JsonValueKind::Object => target
+                    .children
+                    .iter()
+                    .position(|x| x.key.matches_token(json, &token))?,
+                JsonValueKind::Array => {
+                    parse_index(&token).filter(|&x| x < target.children.len())?
+                }
+                _ => return None,
+            };
+            path.push(i);
+            target = &target.children[i];
+        }
+
+        Some(path)
+    }
+
+    /// Build the JSON pointer of the node reached by following `path` from `self`
+    pub fn path_to_json_pointer(&self, json: &str, path: &[usize]) -> Option<String> {
+        let mut pointer = String::new();
+        let mut target = self;
+        for &i in path {
+            target = target.children.get(i)?;
+            pointer.push('/');
+            pointer.push_str(&target.key.to_json_pointer_token(json));
+        }
+
+        Some(pointer)
+    }
+
+    /// Size of this value split by kind of character
+    pub fn breakdown(&self, accounting: Accounting) -> SizeBreakdown {
+        let mut breakdown = SizeBreakdown {
+            whitespace: self.whitespace,
+            comments: self.comments,
+            control: self.control_breakdown(),
+            data_size: self.data_size,
+        };
+        if accounting == Accounting::Member {
+            breakdown.whitespace += self.member.whitespace;
+            breakdown.comments += self.member.comments;
+            breakdown.data_size += self.member.data_size;
+            if let Some(source_control) = self.source_control {
+                breakdown.control.add(&source_control.member);
+                return breakdown;
+            }
+            let mut control_chars = self.member.control_chars;
+            if self.key.key_ptr.is_some() {
+                let colon = self.encoding.unit();
+                breakdown.control.quotes += self.member.quotes;
+                breakdown.control.colons += colon;
+                control_chars -= self.member.quotes + colon;
+            }
+            breakdown.control.commas += control_chars;
+        }
+
+        breakdown
+    }
+
+    fn control_breakdown(&self) -> ControlChars {
+        let unit = self.encoding.unit();
+        let n = self.children.len() * unit;
+        // Control characters of this value that are not inside a child. Commas are counted from
+        // this because of trailing commas.
+        let own_control: usize =
+            self.control_chars - self.children.iter().map(|x| x.control_chars).sum::<usize>();
+        let mut control = if let Some(source_control) = self.source_control {
+            source_control.own
         } else {
-            let percent = "[##########]";
-            //let name = self.key.to_display(json);
-            let name = "Total";
-            let total_size = self.total_size();
-            let line = format!("{:12} {} {}", total_size, percent, name);
-            let mut lines = vec![line];
-            for child in self.children.iter() {
-                let mut percent = b"          ".to_vec();
-                let name = child.key.to_display(json);
-                let size = child.total_size();
-                for i in 0..10 {
-                    if size > total_size * i / percent.len() {
-                        percent[i] = b'#';
+            match self.value_kind {
+                JsonValueKind::String => ControlChars {
+                    quotes: 2 * unit,
+                    ..Default::default()
+                },
+                JsonValueKind::Array => ControlChars {
+                    brackets: 2 * unit,
+                    commas: own_control - 2 * unit,
+                    ..Default::default()
+                },
+                JsonValueKind::Object => {
+                    // Quotes of the keys
+                    let quotes: usize = self.children.iter().map(|x| x.member.quotes).sum();
+                    ControlChars {
+                        quotes,
+                        commas: own_control - 2 * unit - n - quotes,
+                        colons: n,
+                        brackets: 2 * unit,
+                        other: 0,
                     }
                 }
-                let line = format!(
-                    "{:12} [{}] {}",
-                    size,
-                    String::from_utf8(percent).unwrap(),
-                    name
-                );
-                lines.push(line);
+                _ => ControlChars::default(),
+            }
+        };
+        for child in self.children.iter() {
+            control.add(&child.control_breakdown());
+        }
+
+        control
+    }
+
+    pub fn display_list(&self, json: &str, options: &DisplayOptions) -> Vec<String> {
+        let show_entropy = options.entropy || options.sort == SortBy::Entropy;
+        let display_line = |size: usize,
+                            percent: &str,
+                            js: &JsonSize,
+                            accounting: Accounting,
+                            entropy: Option<Entropy>,
+                            name: &str| {
+            let mut line = format!("{:12} {} ", size, percent);
+            if options.breakdown {
+                line.push_str(&js.breakdown(accounting).to_display());
+                line.push(' ');
+            }
+            if let Some(entropy) = entropy {
+                line.push_str(&entropy.to_display());
+                line.push(' ');
+            }
+            if options.memory {
+                let memory = match accounting {
+                    Accounting::Member => memory::member_footprint(js, json),
+                    Accounting::Value => memory::footprint(js, json),
+                };
+                line.push_str(&format!("{:12} ", memory));
+            }
+            if !options.compressors.is_empty() {
+                let minified = minify_dialect(&json[js.span()], options.dialect, false);
+                for compressor in options.compressors.iter() {
+                    line.push_str(&compressor.to_display(minified.as_bytes()));
+                    line.push(' ');
+                }
+            }
+            line.push_str(name);
+            line
+        };
+
+        let mut lines = vec![];
+        if options.breakdown || show_entropy || options.memory || !options.compressors.is_empty() {
+            let mut header = format!("{:>12} {:12} ", "Size", "");
+            if options.breakdown {
+                header.push_str(&SizeBreakdown::header());
+                header.push(' ');
+            }
+            if show_entropy {
+                header.push_str(&Entropy::header());
+                header.push(' ');
+            }
+            if options.memory {
+                header.push_str(&format!("{:>12} ", "Memory"));
+            }
+            for compressor in options.compressors.iter() {
+                header.push_str(&compressor.header());
+                header.push(' ');
             }
-            lines
+            header.push_str("Name");
+            lines.push(header);
         }
+        let percent = "[##########]";
+        //let name = self.key.to_display(json);
+        let name = "Total";
+        let total_size = self.total_size();
+        let entropy = |js: &JsonSize| show_entropy.then(|| Entropy::new(js, json));
+        lines.push(display_line(
+            total_size,
+            percent,
+            self,
+            Accounting::Value,
+            entropy(self),
+            name,
+        ));
+        let mut children: Vec<_> = self
+            .children
+            .iter()
+            .map(|child| (child, entropy(child)))
+            .collect();
+        match options.sort {
+            SortBy::None => {}
+            SortBy::Size => {
+                children.sort_by_key(|(child, _)| std::cmp::Reverse(child.size(options.accounting)))
+            }
+            SortBy::Entropy => {
+                children.sort_by(|(_, a), (_, b)| b.unwrap().bits.total_cmp(&a.unwrap().bits))
+            }
+        }
+        for (child, entropy) in children {
+            let name = child.key.to_display(json);
+            let size = child.size(options.accounting);
+            lines.push(display_line(
+                size,
+                &size_bar(size, total_size),
+                child,
+                options.accounting,
+                entropy,
+                &name,
+            ));
+        }
+
+        lines
     }
 
     pub fn index_json_pointer(&mut self, json: &str, pointer: &str) -> Option<&mut Self> {
@@ -89,7 +703,7 @@ impl JsonSize {
                 JsonValueKind::Object => target
                     .children
                     .iter_mut()
-                    .find(|x| x.key.get_key_str(json).unwrap() == token),
+                    .find(|x| x.key.matches_token(json, &token)),
                 JsonValueKind::Array => {
                     parse_index(&token).and_then(|x| target.children.get_mut(x))
                 }
@@ -98,6 +712,26 @@ impl JsonSize {
     }
 }
 
+/// Bar with 10 slots showing how big `size` is relative to `total_size`
+pub fn size_bar(size: usize, total_size: usize) -> String {
+    let mut percent = b"          ".to_vec();
+    for (i, x) in percent.iter_mut().enumerate() {
+        if size > total_size * i / 10 {
+            *x = b'#';
+        }
+    }
+    format!("[{}]", String::from_utf8(percent).unwrap())
+}
+
+/// Percentage of `total_size` that `size` represents, 0 if `total_size` is 0
+pub fn percent_of(size: usize, total_size: usize) -> f64 {
+    if total_size == 0 {
+        0.0
+    } else {
+        size as f64 * 100.0 / total_size as f64
+    }
+}
+
 fn parse_index(s: &str) -> Option<usize> {
     if s.starts_with('+') || (s.starts_with('0') && s.len() != 1) {
         return None;
@@ -105,61 +739,160 @@ fn parse_index(s: &str) -> Option<usize> {
     s.parse().ok()
 }
 
+/// Position of a value inside its parent: array index or object key
 #[derive(Default, Debug, PartialEq, Eq)]
-struct JsonKey {
+pub struct JsonKey {
     index: usize,
     key_ptr: Option<Span>,
 }
 
 impl JsonKey {
-    fn get_key_str<'a>(&self, json: &'a str) -> Option<&'a str> {
-        if let Some(key_ptr) = &self.key_ptr {
-            let key_ptr_end = find_string_end(*key_ptr, json);
-            Some(&json[key_ptr.start..key_ptr_end])
+    /// Index of this value inside its parent, for both arrays and objects
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Byte range of the key string contents, without quotes. `None` for array items.
+    pub fn key_span(&self) -> Option<Range<usize>> {
+        self.key_ptr.map(|key_ptr| key_ptr.start..key_ptr.end)
+    }
+
+    /// Raw key string, escape sequences are not decoded
+    pub fn get_key_str<'a>(&self, json: &'a str) -> Option<&'a str> {
+        self.key_ptr
+            .as_ref()
+            .map(|key_ptr| &json[key_ptr.start..key_ptr.end])
+    }
+
+    pub fn to_display(&self, json: &str) -> String {
+        if let Some(key) = self.get_key_str(json) {
+            format!("\"{}\"", key)
         } else {
-            None
+            format!("{}", self.index)
         }
     }
 
-    fn to_display(&self, json: &str) -> String {
-        if let Some(key_ptr) = &self.key_ptr {
-            let key_ptr_end = find_string_end(*key_ptr, json);
-            format!("\"{}\"", &json[key_ptr.start..key_ptr_end])
+    /// Compare an object key with an already unescaped JSON pointer token
+    pub(crate) fn matches_token(&self, json: &str, token: &str) -> bool {
+        match self.get_key_str(json) {
+            Some(key) if key.contains('\\') => unescape_json_string(key) == token,
+            Some(key) => key == token,
+            None => false,
+        }
+    }
+
+    /// Reference token that selects this value in a JSON pointer (RFC 6901)
+    pub fn to_json_pointer_token(&self, json: &str) -> String {
+        if let Some(key) = self.get_key_str(json) {
+            unescape_json_string(key)
+                .replace('~', "~0")
+                .replace('/', "~1")
         } else {
             format!("{}", self.index)
         }
     }
 }
 
-fn find_string_end(key_ptr: Span, json: &str) -> usize {
-    let mut key_ptr = key_ptr.start;
-    let b = json.as_bytes();
-    let mut escape_next = false;
+/// Append `s` as a JSON string, with quotes and only the escape sequences that are required
+pub(crate) fn push_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
 
-    loop {
-        let c = b[key_ptr];
-        if escape_next {
-            escape_next = false;
-            key_ptr += 1;
+/// A string value (`span` text, with quotes) written with the fewest escape sequences and double
+/// quotes, so that equal strings written differently compare equal
+pub(crate) fn canonical_json_string(raw: &str) -> Cow<'_, str> {
+    if raw.starts_with('"') && !raw.contains('\\') {
+        return Cow::Borrowed(raw);
+    }
+    let mut out = String::with_capacity(raw.len());
+    push_json_string(&mut out, &unescape_json_string(&raw[1..raw.len() - 1]));
+
+    Cow::Owned(out)
+}
+
+/// Decode the escape sequences of the contents of a JSON string.
+/// Invalid escape sequences are replaced with U+FFFD.
+pub fn unescape_json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
             continue;
         }
-        if c == b'"' {
-            return key_ptr;
-        }
-        if c == b'\\' {
-            escape_next = true;
+        match chars.next() {
+            Some('"') => out.push('"'),
+            // Only in JSON5 single quoted strings
+            Some('\'') => out.push('\''),
+            Some('\\') => out.push('\\'),
+            Some('/') => out.push('/'),
+            Some('b') => out.push('\u{8}'),
+            Some('f') => out.push('\u{c}'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('u') => {
+                let hi = parse_hex4(&mut chars);
+                let c = match hi {
+                    Some(hi @ 0xD800..=0xDBFF) => {
+                        // Surrogate pair, the low half must follow as another \u escape
+                        let mut lookahead = chars.clone();
+                        let lo = if lookahead.next() == Some('\\') && lookahead.next() == Some('u')
+                        {
+                            parse_hex4(&mut lookahead)
+                        } else {
+                            None
+                        };
+                        match lo {
+                            Some(lo @ 0xDC00..=0xDFFF) => {
+                                chars = lookahead;
+                                char::from_u32(0x10000 + ((hi - 0xD800) << 10) + (lo - 0xDC00))
+                            }
+                            _ => None,
+                        }
+                    }
+                    Some(x) => char::from_u32(x),
+                    None => None,
+                };
+                out.push(c.unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            _ => out.push(char::REPLACEMENT_CHARACTER),
         }
-        key_ptr += 1;
     }
+
+    out
+}
+
+fn parse_hex4(chars: &mut std::str::Chars) -> Option<u32> {
+    let mut x = 0;
+    for _ in 0..4 {
+        x = x * 16 + chars.next()?.to_digit(16)?;
+    }
+    Some(x)
 }
 
+/// Byte range `start..end` inside the JSON input
 #[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
 struct Span {
     start: usize,
+    end: usize,
 }
 
-#[derive(Default, Debug, PartialEq, Eq)]
-enum JsonValueKind {
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Hash)]
+pub enum JsonValueKind {
     #[default]
     Null,
     Boolean,
@@ -170,22 +903,16 @@ enum JsonValueKind {
 }
 
 fn parse_json_size<I>(
-    mut chars: &mut Peekable<I>,
+    chars: &mut Peekable<I>,
     recursion_level: usize,
-) -> (JsonSize, Option<char>, bool)
+    dialect: Dialect,
+) -> Result<(JsonSize, Option<char>, bool), ()>
 where
     I: Iterator<Item = (usize, char)>,
 {
-    let mut js = JsonSize {
-        whitespace: 0,
-        control_chars: 0,
-        children: vec![],
-        value_kind: JsonValueKind::Null,
-        data_size: 0,
-        data_ptr: Span::default(),
-        key: JsonKey::default(),
-    };
+    let mut js = JsonSize::default();
     let mut is_empty = true;
+    let json5 = dialect == Dialect::Json5;
 
     loop {
         let c = chars.peek();
@@ -193,12 +920,24 @@ where
         if c.is_none() {
             break;
         }
-        let (c_ptr, c) = *c.unwrap();
+        let c = c.unwrap().1;
+        let starts_value = match c {
+            't' | 'f' | 'n' | '"' | '-' | '0'..='9' | '[' | '{' => true,
+            '\'' | '+' | '.' | 'I' | 'N' => json5,
+            _ => false,
+        };
+        if starts_value && !is_empty {
+            // Only one value is allowed
+            return Err(());
+        }
         match c {
             ' ' | '\n' | '\r' | '\t' => {
                 js.whitespace += 1;
                 chars.next().unwrap();
             }
+            '/' if dialect.allows_comments() => {
+                parse_comment(chars, &mut js)?;
+            }
             't' | 'f' | 'n' => {
                 js.value_kind = if c == 'n' {
                     JsonValueKind::Null
@@ -206,69 +945,86 @@ where
                     JsonValueKind::Boolean
                 };
                 is_empty = false;
-                parse_any_keyword(&mut chars, &mut js).unwrap();
+                parse_any_keyword(chars, &mut js)?;
             }
-            '"' => {
+            '"' | '\'' if starts_value => {
                 js.value_kind = JsonValueKind::String;
                 is_empty = false;
-                parse_string(&mut chars, &mut js).unwrap();
+                parse_string(chars, &mut js)?;
             }
-            '-' | '0'..='9' => {
+            '-' | '0'..='9' | '+' | '.' | 'I' | 'N' if starts_value => {
                 js.value_kind = JsonValueKind::Number;
                 is_empty = false;
-                parse_number(&mut chars, &mut js).unwrap();
+                parse_number(chars, &mut js, dialect)?;
             }
             '[' => {
                 js.value_kind = JsonValueKind::Array;
                 is_empty = false;
-                parse_array(&mut chars, &mut js, recursion_level + 1).unwrap();
+                parse_array(chars, &mut js, recursion_level + 1, dialect)?;
             }
             '{' => {
                 js.value_kind = JsonValueKind::Object;
                 is_empty = false;
-                parse_object(&mut chars, &mut js, recursion_level + 1).unwrap();
+                parse_object(chars, &mut js, recursion_level + 1, dialect)?;
             }
             ',' | ']' | '}' => {
                 if recursion_level == 0 {
-                    panic!("Invalid json");
+                    return Err(());
                 } else {
-                    return (js, Some(c), is_empty);
+                    return Ok((js, Some(c), is_empty));
                 }
             }
-            _ => todo!(),
+            _ => return Err(()),
         }
     }
 
     if recursion_level == 0 {
-        (js, None, is_empty)
+        Ok((js, None, is_empty))
     } else {
-        panic!("Invalid json");
+        Err(())
     }
 }
 
 fn parse_array<I>(
-    mut chars: &mut Peekable<I>,
+    chars: &mut Peekable<I>,
     js: &mut JsonSize,
     recursion_level: usize,
+    dialect: Dialect,
 ) -> Result<(), ()>
 where
     I: Iterator<Item = (usize, char)>,
 {
     // Save start index
     let data_ptr = chars.peek().ok_or(())?.0;
-    let mut len = 0;
     // Remove leading [
     match chars.next().ok_or(())?.1 {
         '[' => (),
         _ => return Err(()),
     }
 
+    let mut commas = 0;
     loop {
-        let (mut child, last_char, is_empty) = parse_json_size(chars, recursion_level);
+        let (mut child, last_char, is_empty) = parse_json_size(chars, recursion_level, dialect)?;
+        if is_empty && commas > 0 {
+            // `[1,]` is only valid with trailing commas, `[1,,2]` is never valid
+            if last_char == Some(',') || !dialect.allows_trailing_commas() {
+                return Err(());
+            }
+            // The whitespace after the trailing comma belongs to the last item
+            let last = js.children.last_mut().unwrap();
+            last.member.whitespace += child.whitespace;
+            last.member.comments += child.comments;
+        } else if is_empty && last_char == Some(',') {
+            // `[,]`
+            return Err(());
+        }
         child.key = JsonKey {
             index: js.children.len(),
             key_ptr: None,
         };
+        if last_char == Some(',') {
+            child.member.control_chars += 1;
+        }
         js.add_stats_from(&child);
         if !is_empty {
             js.children.push(child);
@@ -280,6 +1036,7 @@ where
                 ',' => (),
                 _ => return Err(()),
             }
+            commas += 1;
             continue;
         } else {
             break;
@@ -287,51 +1044,75 @@ where
     }
 
     // Remove final ]
-    match chars.next().ok_or(())?.1 {
-        ']' => (),
+    let data_end = match chars.next().ok_or(())? {
+        (i, ']') => i + 1,
         _ => return Err(()),
-    }
+    };
+    js.data_ptr = Span {
+        start: data_ptr,
+        end: data_end,
+    };
 
-    // Open and close array, and one comma per item except one
-    js.control_chars += 2 + js.children.len().saturating_sub(1);
+    // Open and close array, and one comma per item except one (unless there is a trailing comma)
+    js.control_chars += 2 + commas;
 
     Ok(())
 }
 
 fn parse_object<I>(
-    mut chars: &mut Peekable<I>,
+    chars: &mut Peekable<I>,
     js: &mut JsonSize,
     recursion_level: usize,
+    dialect: Dialect,
 ) -> Result<(), ()>
 where
     I: Iterator<Item = (usize, char)>,
 {
     // Save start index
     let data_ptr = chars.peek().ok_or(())?.0;
-    let mut len = 0;
     // Remove leading {
     match chars.next().ok_or(())?.1 {
         '{' => (),
         _ => return Err(()),
     }
 
+    let mut commas = 0;
     loop {
         let mut key_js = JsonSize::default();
         // Optional whitespace
-        skip_whitespace(chars, &mut key_js);
-        let key_ptr = chars.peek().ok_or(())?.0;
-        // Check for } (empty object)
-        match chars.peek().ok_or(())?.1 {
-            '}' => {
-                js.add_stats_from(&key_js);
-                break;
-            }
-            _ => (),
-        }
-        // Remove "key"
-        parse_string(chars, &mut key_js)?;
+        skip_whitespace(chars, &mut key_js, dialect)?;
+        // Check for } (empty object or trailing comma)
+        if chars.peek().ok_or(())?.1 == '}' {
+            if commas > 0 {
+                if !dialect.allows_trailing_commas() {
+                    return Err(());
+                }
+                // The whitespace after the trailing comma belongs to the last member
+                let last = js.children.last_mut().unwrap();
+                last.member.whitespace += key_js.whitespace;
+                last.member.comments += key_js.comments;
+            }
+            js.add_stats_from(&key_js);
+            break;
+        }
+        // Remove "key", or an unquoted key in JSON5
+        let quotes = match chars.peek().ok_or(())?.1 {
+            '"' => 2,
+            '\'' if dialect == Dialect::Json5 => 2,
+            c if dialect == Dialect::Json5 && is_identifier_start(c) => 0,
+            _ => return Err(()),
+        };
+        if quotes == 2 {
+            parse_string(chars, &mut key_js)?;
+        } else {
+            parse_identifier(chars, &mut key_js)?;
+        }
+        let key_ptr = Span {
+            start: key_js.data_ptr.start + quotes / 2,
+            end: key_js.data_ptr.end - quotes / 2,
+        };
         // Optional whitespace
-        skip_whitespace(chars, &mut key_js);
+        skip_whitespace(chars, &mut key_js, dialect)?;
         // Remove :
         match chars.next().ok_or(())?.1 {
             ':' => (),
@@ -340,15 +1121,26 @@ where
         js.add_stats_from(&key_js);
 
         // Remove value
-        let (mut child, last_char, is_empty) = parse_json_size(chars, recursion_level);
+        let (mut child, last_char, is_empty) = parse_json_size(chars, recursion_level, dialect)?;
+        if is_empty {
+            // Key without value
+            return Err(());
+        }
+        child.member = MemberOverhead {
+            whitespace: key_js.whitespace,
+            comments: key_js.comments,
+            quotes,
+            // Quotes, colon and comma
+            control_chars: key_js.control_chars + 1 + usize::from(last_char == Some(',')),
+            data_size: key_js.data_size,
+        };
         child.key = JsonKey {
             index: js.children.len(),
-            key_ptr: Some(Span { start: key_ptr + 1 }),
+            // Only the contents of the string, without quotes
+            key_ptr: Some(key_ptr),
         };
         js.add_stats_from(&child);
-        if !is_empty {
-            js.children.push(child);
-        }
+        js.children.push(child);
 
         if last_char == Some(',') {
             // Remove comma
@@ -356,6 +1148,7 @@ where
                 ',' => (),
                 _ => return Err(()),
             }
+            commas += 1;
             continue;
         } else {
             break;
@@ -363,126 +1156,135 @@ where
     }
 
     // Remove final }
-    match chars.next().ok_or(())?.1 {
-        '}' => (),
+    let data_end = match chars.next().ok_or(())? {
+        (i, '}') => i + 1,
         _ => return Err(()),
-    }
+    };
+    js.data_ptr = Span {
+        start: data_ptr,
+        end: data_end,
+    };
 
-    // Open and close object, one colon per item, and one comma per item except one
-    js.control_chars += 2 + js.children.len() + js.children.len().saturating_sub(1);
+    // Open and close object, one colon per item, and one comma per item except one (unless there
+    // is a trailing comma)
+    js.control_chars += 2 + js.children.len() + commas;
 
     Ok(())
 }
 
-fn parse_number<I>(mut chars: &mut Peekable<I>, js: &mut JsonSize) -> Result<(), ()>
+/// Remove characters while `pred` is true, returns the number of bytes removed
+fn take_while<I>(chars: &mut Peekable<I>, pred: impl Fn(char) -> bool) -> usize
 where
     I: Iterator<Item = (usize, char)>,
 {
-    // Save start index
-    let data_ptr = chars.peek().ok_or(())?.0;
     let mut len = 0;
-    // Remove optional leading minus sign
-    match chars.peek().ok_or(())?.1 {
-        '-' => {
-            let c = chars.next().unwrap().1;
-            len += c.len_utf8();
+    while let Some(&(_, c)) = chars.peek() {
+        if !pred(c) {
+            break;
         }
-        _ => (),
+        len += c.len_utf8();
+        chars.next();
     }
+    len
+}
 
-    // Remove integer part
-    // Allow leading 0, we assume the json is valid at this point
-    loop {
-        match chars.peek().map(|uc| uc.1) {
-            Some('0'..='9') => {
-                let c = chars.next().unwrap().1;
-                len += c.len_utf8();
-            }
-            None => {
-                js.data_size += len;
-                js.control_chars += 0;
-                js.data_ptr = Span { start: data_ptr };
-                return Ok(());
-            }
-            _ => break,
-        }
+fn parse_number<I>(chars: &mut Peekable<I>, js: &mut JsonSize, dialect: Dialect) -> Result<(), ()>
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    let json5 = dialect == Dialect::Json5;
+    // Save start index
+    let data_ptr = chars.peek().ok_or(())?.0;
+    // Remove optional sign, JSON5 also allows a leading plus sign
+    let mut len = take_while(chars, |c| c == '-' || (json5 && c == '+'));
+    if len > 1 {
+        return Err(());
     }
 
-    // Remove optional fraction part
-    match chars.peek().map(|uc| uc.1) {
-        Some('.') => {
-            let c = chars.next().unwrap().1;
-            len += c.len_utf8();
-
-            loop {
-                match chars.peek().map(|uc| uc.1) {
-                    Some('0'..='9') => {
-                        let c = chars.next().unwrap().1;
-                        len += c.len_utf8();
-                    }
-                    None => {
-                        js.data_size += len;
-                        js.control_chars += 0;
-                        js.data_ptr = Span { start: data_ptr };
-                        return Ok(());
+    let first = chars.peek().map(|uc| uc.1);
+    let mut digits = 0;
+    match first {
+        Some('I') if json5 => {
+            let mut keyword = JsonSize::default();
+            parse_keyword("Infinity", chars, &mut keyword)?;
+            len += keyword.data_size;
+            digits += 1;
+        }
+        Some('N') if json5 => {
+            let mut keyword = JsonSize::default();
+            parse_keyword("NaN", chars, &mut keyword)?;
+            len += keyword.data_size;
+            digits += 1;
+        }
+        _ => {
+            // Remove integer part
+            // Allow leading 0, we assume the json is valid at this point
+            digits += take_while(chars, |c| c.is_ascii_digit());
+            len += digits;
+
+            if json5 && first == Some('0') && digits == 1 {
+                // Hexadecimal
+                if let Some('x' | 'X') = chars.peek().map(|uc| uc.1) {
+                    chars.next();
+                    let hex_digits = take_while(chars, |c| c.is_ascii_hexdigit());
+                    if hex_digits == 0 {
+                        return Err(());
                     }
-                    _ => break,
+                    len += 1 + hex_digits;
                 }
             }
-        }
-        _ => (),
-    }
-
-    // Remove optional exponent part
-    match chars.peek().map(|uc| uc.1) {
-        Some('e' | 'E') => {
-            let c = chars.next().unwrap().1;
-            len += c.len_utf8();
 
-            // Remove optional sign
-            match chars.peek().ok_or(())?.1 {
-                '-' | '+' => {
-                    let c = chars.next().unwrap().1;
-                    len += c.len_utf8();
+            // Remove optional fraction part, JSON5 allows both leading and trailing dots
+            if let Some('.') = chars.peek().map(|uc| uc.1) {
+                chars.next();
+                let fraction = take_while(chars, |c| c.is_ascii_digit());
+                if !json5 && (digits == 0 || fraction == 0) {
+                    return Err(());
                 }
-                _ => (),
+                len += 1 + fraction;
+                digits += fraction;
             }
 
-            // Remove exponent digits
-            loop {
-                match chars.peek().map(|uc| uc.1) {
-                    Some('0'..='9') => {
-                        let c = chars.next().unwrap().1;
-                        len += c.len_utf8();
-                    }
-                    None => {
-                        js.data_size += len;
-                        js.control_chars += 0;
-                        js.data_ptr = Span { start: data_ptr };
-                        return Ok(());
-                    }
-                    _ => break,
+            // Remove optional exponent part
+            if let Some('e' | 'E') = chars.peek().map(|uc| uc.1) {
+                chars.next();
+                len += 1;
+                // Remove optional sign
+                if let Some('-' | '+') = chars.peek().map(|uc| uc.1) {
+                    chars.next();
+                    len += 1;
                 }
+                // Remove exponent digits
+                let exponent = take_while(chars, |c| c.is_ascii_digit());
+                if exponent == 0 {
+                    return Err(());
+                }
+                len += exponent;
             }
         }
-        _ => (),
+    }
+    if digits == 0 {
+        return Err(());
     }
 
     js.data_size += len;
-    js.control_chars += 0;
-    js.data_ptr = Span { start: data_ptr };
+    js.data_ptr = Span {
+        start: data_ptr,
+        end: data_ptr + len,
+    };
 
     Ok(())
 }
 
-fn parse_string<I>(mut chars: &mut Peekable<I>, js: &mut JsonSize) -> Result<(), ()>
+/// Strings start and end with `"`, or with `'` in JSON5
+fn parse_string<I>(chars: &mut Peekable<I>, js: &mut JsonSize) -> Result<(), ()>
 where
     I: Iterator<Item = (usize, char)>,
 {
-    match chars.next().ok_or(())?.1 {
-        '"' => (),
+    let (data_ptr, quote) = match chars.next().ok_or(())? {
+        (i, q @ ('"' | '\'')) => (i, q),
         _ => return Err(()),
-    }
+    };
 
     let mut escape_next = false;
     let mut len = 0;
@@ -502,16 +1304,75 @@ where
             escape_next = true;
             continue;
         }
-        if c == '"' {
+        if c == quote {
             len -= 1;
             js.data_size += len;
             js.control_chars += 2;
+            js.data_ptr = Span {
+                start: data_ptr,
+                end: data_ptr + len + 2,
+            };
             return Ok(());
         }
     }
 }
 
-fn parse_any_keyword<I>(mut chars: &mut Peekable<I>, js: &mut JsonSize) -> Result<(), ()>
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '$'
+}
+
+/// Unquoted JSON5 object key, counted as data
+fn parse_identifier<I>(chars: &mut Peekable<I>, js: &mut JsonSize) -> Result<(), ()>
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    let data_ptr = chars.peek().ok_or(())?.0;
+    let len = take_while(chars, |c| is_identifier_start(c) || c.is_alphanumeric());
+    if len == 0 {
+        return Err(());
+    }
+    js.data_size += len;
+    js.data_ptr = Span {
+        start: data_ptr,
+        end: data_ptr + len,
+    };
+
+    Ok(())
+}
+
+/// `// line comment` or `/* block comment */`. The newline that ends a line comment is
+/// whitespace.
+fn parse_comment<I>(chars: &mut Peekable<I>, js: &mut JsonSize) -> Result<(), ()>
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    match chars.next().ok_or(())?.1 {
+        '/' => (),
+        _ => return Err(()),
+    }
+    let len = match chars.next().ok_or(())?.1 {
+        '/' => take_while(chars, |c| c != '\n'),
+        '*' => {
+            let mut len = 0;
+            let mut prev = ' ';
+            loop {
+                let c = chars.next().ok_or(())?.1;
+                len += c.len_utf8();
+                if prev == '*' && c == '/' {
+                    break;
+                }
+                prev = c;
+            }
+            len
+        }
+        _ => return Err(()),
+    };
+    js.comments += 2 + len;
+
+    Ok(())
+}
+
+fn parse_any_keyword<I>(chars: &mut Peekable<I>, js: &mut JsonSize) -> Result<(), ()>
 where
     I: Iterator<Item = (usize, char)>,
 {
@@ -523,10 +1384,15 @@ where
     }
 }
 
-fn parse_keyword<I>(keyword: &'static str, mut chars: I, js: &mut JsonSize) -> Result<(), ()>
+fn parse_keyword<I>(
+    keyword: &'static str,
+    chars: &mut Peekable<I>,
+    js: &mut JsonSize,
+) -> Result<(), ()>
 where
     I: Iterator<Item = (usize, char)>,
 {
+    let data_ptr = chars.peek().ok_or(())?.0;
     let keyword_len = keyword.len();
     let mut keyword_chars = keyword.chars();
 
@@ -535,6 +1401,10 @@ where
         let next_k = keyword_chars.next();
         if next_k.is_none() {
             js.data_size += keyword_len;
+            js.data_ptr = Span {
+                start: data_ptr,
+                end: data_ptr + keyword_len,
+            };
             return Ok(());
         }
         let next_i = chars.next().map(|uc| uc.1);
@@ -545,14 +1415,18 @@ where
     }
 }
 
-fn skip_whitespace<I>(mut chars: &mut Peekable<I>, js: &mut JsonSize)
+fn skip_whitespace<I>(
+    chars: &mut Peekable<I>,
+    js: &mut JsonSize,
+    dialect: Dialect,
+) -> Result<(), ()>
 where
     I: Iterator<Item = (usize, char)>,
 {
     loop {
         let c = chars.peek();
         if c.is_none() {
-            return;
+            return Ok(());
         }
         let c = c.unwrap().1;
         match c {
@@ -560,20 +1434,15 @@ where
                 js.whitespace += 1;
                 chars.next().unwrap();
             }
-            _ => return,
+            '/' if dialect.allows_comments() => parse_comment(chars, js)?,
+            _ => return Ok(()),
         }
     }
 }
 
 fn assert_total_size_invariant(json: &str, js: &JsonSize) {
-    // Invariant: whitespace + control_chars + data_size == input.len()
-    assert_eq!(
-        json.len(),
-        js.whitespace + js.control_chars + js.data_size,
-        "{:?}\n{:?}",
-        json,
-        js
-    );
+    // Invariant: whitespace + comments + control_chars + data_size == input.len()
+    assert_eq!(json.len(), js.total_size(), "{:?}\n{:?}", json, js);
 }
 
 #[cfg(test)]
@@ -750,4 +1619,183 @@ mod tests {
         assert_eq!(js.value_kind, JsonValueKind::String);
         assert_eq!(js.children.len(), 0);
     }
+
+    #[test]
+    fn test_value_spans() {
+        let json = r#" {"a": [1.5, "x\"y"], "b\/c": true} "#;
+        let js = JsonSize::new(json);
+        assert_eq!(&json[js.span()], &json[1..json.len() - 1]);
+        let a = &js.children()[0];
+        assert_eq!(&json[a.span()], r#"[1.5, "x\"y"]"#);
+        assert_eq!(&json[a.children()[0].span()], "1.5");
+        assert_eq!(&json[a.children()[1].span()], r#""x\"y""#);
+        let b = &js.children()[1];
+        assert_eq!(&json[b.span()], "true");
+        assert_eq!(b.key().get_key_str(json), Some(r#"b\/c"#));
+        assert_eq!(js.json_pointer_path(json, "/b~1c"), Some(vec![1]));
+        assert_eq!(js.path_to_json_pointer(json, &[1]).unwrap(), "/b~1c");
+    }
+
+    #[test]
+    fn test_unescape_json_string() {
+        assert_eq!(unescape_json_string(r#"a\"b\\c\/d\n"#), "a\"b\\c/d\n");
+        assert_eq!(
+            unescape_json_string(r#"\u00e9\ud83d\ude00"#),
+            "\u{e9}\u{1f600}"
+        );
+        assert_eq!(unescape_json_string(r#"\ud83d"#), "\u{fffd}");
+    }
+
+    fn assert_members_add_up(json: &str, js: &JsonSize) {
+        if !js.children.is_empty() {
+            let sum: usize = js.children.iter().map(|x| x.member_size()).sum();
+            // Everything except the brackets
+            assert_eq!(sum, js.span().len() - 2, "{:?}", &json[js.span()]);
+        }
+        for child in js.children.iter() {
+            assert_members_add_up(json, child);
+        }
+    }
+
+    #[test]
+    fn test_member_size() {
+        let json = r#"{ "s" : "abc" , "n": [1, 2,3 ], "o": {"x": {}} }"#;
+        let js = JsonSize::new(json);
+        assert_members_add_up(json, &js);
+
+        let s = &js.children[0];
+        assert_eq!(s.total_size(), r#" "abc" "#.len());
+        assert_eq!(s.member_size(), r#" "s" : "abc" ,"#.len());
+        assert_eq!(s.size(Accounting::Value), s.total_size());
+        let n = &js.children[1];
+        assert_eq!(n.children[0].member_size(), "1,".len());
+        assert_eq!(n.children[2].member_size(), "3 ".len());
+        let o = &js.children[2];
+        assert_eq!(o.member_size(), r#" "o": {"x": {}} "#.len());
+    }
+
+    #[test]
+    fn test_breakdown() {
+        let json = r#"{"a": [1, "x", {"b": null}], "cd": ""}"#;
+        let js = JsonSize::new(json);
+        let breakdown = js.breakdown(Accounting::Value);
+        assert_eq!(
+            breakdown,
+            SizeBreakdown {
+                whitespace: 6,
+                comments: 0,
+                control: ControlChars {
+                    quotes: 2 + 2 + 2 + 2 + 2,
+                    commas: 3,
+                    colons: 3,
+                    brackets: 6,
+                    other: 0,
+                },
+                data_size: 1 + 1 + 1 + 1 + 4 + 2,
+            }
+        );
+        assert_eq!(breakdown.control.total(), js.control_chars);
+        assert_eq!(breakdown.total_size(), json.len());
+
+        let a = &js.children[0];
+        let member = a.breakdown(Accounting::Member);
+        assert_eq!(member.total_size(), a.member_size());
+        assert_eq!(member.control.quotes, 2 + 2 + 2);
+        assert_eq!(member.control.commas, 2 + 1);
+        let cd = &js.children[1];
+        assert_eq!(cd.breakdown(Accounting::Member).control.commas, 0);
+    }
+
+    #[test]
+    fn test_comments() {
+        let json = "// header\n{\"a\": 1, /* b */ \"b\": [2, 3,], // trailing\n}";
+        assert_eq!(JsonSize::try_new(json), None);
+        assert_eq!(JsonSize::try_with_dialect("[1,,2]", Dialect::Jsonc), None);
+        assert_eq!(JsonSize::try_with_dialect("[,]", Dialect::Jsonc), None);
+        assert_eq!(
+            JsonSize::try_with_dialect("{\"a\": 1,}", Dialect::Json),
+            None
+        );
+
+        let js = JsonSize::with_dialect(json, Dialect::Jsonc);
+        assert_members_add_up(json, &js);
+        assert_eq!(js.comments, "// header/* b */// trailing".len());
+        assert_eq!(js.minified_size(), r#"{"a":1,"b":[2,3,],}"#.len());
+        let b = &js.children[1];
+        assert_eq!(b.children.len(), 2);
+        assert_eq!(
+            b.member_size(),
+            r#" /* b */ "b": [2, 3,], // trailing"#.len() + 1
+        );
+
+        let breakdown = js.breakdown(Accounting::Value);
+        assert_eq!(breakdown.total_size(), json.len());
+        assert_eq!(breakdown.control.commas, 4);
+        assert_eq!(breakdown.control.total(), js.control_chars);
+    }
+
+    #[test]
+    fn test_json5() {
+        let json = "{unquoted: 'single \\' quote', $id: 0x1F, n: [+1, .5, 5., -Infinity, NaN]}";
+        assert_eq!(JsonSize::try_with_dialect(json, Dialect::Jsonc), None);
+        let js = JsonSize::with_dialect(json, Dialect::Json5);
+        assert_members_add_up(json, &js);
+        assert_eq!(js.children[0].key().get_key_str(json), Some("unquoted"));
+        assert_eq!(js.children[0].value_kind, JsonValueKind::String);
+        assert_eq!(js.children[1].key().get_key_str(json), Some("$id"));
+        assert_eq!(&json[js.children[1].span()], "0x1F");
+        let n = &js.children[2];
+        let numbers: Vec<_> = n.children.iter().map(|x| &json[x.span()]).collect();
+        assert_eq!(numbers, vec!["+1", ".5", "5.", "-Infinity", "NaN"]);
+        assert!(n
+            .children
+            .iter()
+            .all(|x| x.value_kind == JsonValueKind::Number));
+        assert_eq!(js.json_pointer_path(json, "/n/4"), Some(vec![2, 4]));
+
+        let breakdown = js.breakdown(Accounting::Value);
+        assert_eq!(breakdown.control.quotes, 2);
+        assert_eq!(breakdown.total_size(), json.len());
+        let member = js.children[1].breakdown(Accounting::Member);
+        assert_eq!(member.control.quotes, 0);
+        assert_eq!(member.total_size(), js.children[1].member_size());
+    }
+
+    #[test]
+    fn test_encoding() {
+        let json = "{\"\u{e9}\": [\"\u{1f600}\", 1]}";
+        let utf8 = JsonSize::new(json);
+        let utf16 =
+            JsonSize::try_with_encoding(json, Dialect::Json, Encoding::Utf16Le, true).unwrap();
+        assert_eq!(
+            utf16.total_size(),
+            Encoding::Utf16Le.encode(json, true).len()
+        );
+        assert_eq!(utf16.whitespace, 2 + 2 * 2);
+        assert_eq!(utf16.data_size, 2 + 4 + 2);
+        assert_eq!(utf16.children[0].span(), utf8.children[0].span());
+        assert_members_add_up_encoded(&utf16, Encoding::Utf16Le, json);
+
+        let breakdown = utf16.breakdown(Accounting::Value);
+        assert_eq!(breakdown.control.total(), utf16.control_chars);
+        assert_eq!(breakdown.control.commas, 2);
+        let member = utf16.children[0].breakdown(Accounting::Member);
+        assert_eq!(member.total_size(), utf16.children[0].member_size());
+        assert_eq!(member.control.colons, 2);
+
+        let utf32 =
+            JsonSize::try_with_encoding(json, Dialect::Json, Encoding::Utf32Be, false).unwrap();
+        assert_eq!(utf32.total_size(), json.chars().count() * 4);
+    }
+
+    fn assert_members_add_up_encoded(js: &JsonSize, encoding: Encoding, json: &str) {
+        if !js.children.is_empty() {
+            let sum: usize = js.children.iter().map(|x| x.member_size()).sum();
+            let inside = &json[js.span().start + 1..js.span().end - 1];
+            assert_eq!(sum, encoding.encoded_len(inside));
+        }
+        for child in js.children.iter() {
+            assert_members_add_up_encoded(child, encoding, json);
+        }
+    }
 }
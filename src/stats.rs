@@ -0,0 +1,204 @@
+//! Summary statistics of a JSON value: bytes and count per kind of value, and the extremes
+//! (deepest value, largest array, longest string) with their location.
+use crate::size::{percent_of, size_bar, JsonSize, JsonValueKind};
+
+const KINDS: [JsonValueKind; 6] = [
+    JsonValueKind::Null,
+    JsonValueKind::Boolean,
+    JsonValueKind::Number,
+    JsonValueKind::String,
+    JsonValueKind::Object,
+    JsonValueKind::Array,
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KindStats {
+    pub count: usize,
+    /// Bytes that belong to values of this kind but not to their children. For objects this
+    /// includes the keys, so the bytes of all the kinds add up to the total size.
+    pub bytes: usize,
+}
+
+/// A record value and the path of child indices where it was found
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Extreme {
+    pub value: usize,
+    pub path: Vec<usize>,
+}
+
+impl Extreme {
+    /// Replace `extreme` if `value` is bigger, keeping the first one on ties
+    fn update(extreme: &mut Option<Extreme>, value: usize, path: &[usize]) {
+        match extreme {
+            Some(x) if x.value >= value => {}
+            _ => {
+                *extreme = Some(Extreme {
+                    value,
+                    path: path.to_vec(),
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// Indexed by the position of the kind in `KINDS`
+    pub kinds: [KindStats; 6],
+    pub total_size: usize,
+    pub nodes: usize,
+    /// Depth of the deepest value, the root has depth 0
+    pub max_depth: Extreme,
+    /// Number of items of the largest array
+    pub largest_array: Option<Extreme>,
+    /// Bytes of the longest string, without quotes
+    pub longest_string: Option<Extreme>,
+}
+
+impl Stats {
+    pub fn new(js: &JsonSize) -> Self {
+        let mut stats = Stats {
+            total_size: js.total_size(),
+            ..Default::default()
+        };
+        let mut path = vec![];
+        stats.visit(js, &mut path);
+
+        stats
+    }
+
+    fn visit(&mut self, js: &JsonSize, path: &mut Vec<usize>) {
+        let kind = js.value_kind();
+        let children_size: usize = js.children().iter().map(|x| x.total_size()).sum();
+        let kind_stats = &mut self.kinds[KINDS.iter().position(|&x| x == kind).unwrap()];
+        kind_stats.count += 1;
+        kind_stats.bytes += js.total_size() - children_size;
+        self.nodes += 1;
+        if path.len() > self.max_depth.value {
+            self.max_depth = Extreme {
+                value: path.len(),
+                path: path.clone(),
+            };
+        }
+        match kind {
+            JsonValueKind::Array => {
+                Extreme::update(&mut self.largest_array, js.children().len(), path)
+            }
+            JsonValueKind::String => {
+                Extreme::update(&mut self.longest_string, js.span().len() - 2, path)
+            }
+            _ => {}
+        }
+
+        for (i, child) in js.children().iter().enumerate() {
+            path.push(i);
+            self.visit(child, path);
+            path.pop();
+        }
+    }
+
+    /// Format the statistics, `pointer` is the location of `js` inside `root` and is prepended to
+    /// all the paths
+    pub fn display_list(&self, js: &JsonSize, json: &str, pointer: &str) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{:>12} {:12} {:>10} {:>6} Kind",
+            "Bytes", "", "Count", "%"
+        )];
+        for (kind, stats) in KINDS.iter().zip(self.kinds.iter()) {
+            lines.push(format!(
+                "{:12} {} {:10} {:5.1}% {:?}",
+                stats.bytes,
+                size_bar(stats.bytes, self.total_size),
+                stats.count,
+                percent_of(stats.bytes, self.total_size),
+                kind
+            ));
+        }
+        lines.push(format!(
+            "{:12} {} {:10} {:5.1}% Total",
+            self.total_size,
+            size_bar(self.total_size, self.total_size),
+            self.nodes,
+            percent_of(self.total_size, self.total_size),
+        ));
+
+        let location = |extreme: &Extreme| {
+            format!(
+                "{}{}",
+                pointer,
+                js.path_to_json_pointer(json, &extreme.path).unwrap()
+            )
+        };
+        lines.push(String::new());
+        lines.push(format!("Total nodes: {}", self.nodes));
+        lines.push(format!(
+            "Max depth: {} at {:?}",
+            self.max_depth.value,
+            location(&self.max_depth)
+        ));
+        if let Some(largest_array) = &self.largest_array {
+            lines.push(format!(
+                "Largest array: {} items at {:?}",
+                largest_array.value,
+                location(largest_array)
+            ));
+        }
+        if let Some(longest_string) = &self.longest_string {
+            lines.push(format!(
+                "Longest string: {} bytes at {:?}",
+                longest_string.value,
+                location(longest_string)
+            ));
+        }
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let json = r#"{"a": [1, 2, [true]], "b": {"c": {"d": "long string"}}, "e": null}"#;
+        let js = JsonSize::new(json);
+        let stats = Stats::new(&js);
+
+        assert_eq!(stats.nodes, 10);
+        assert_eq!(
+            stats.kinds.iter().map(|x| x.bytes).sum::<usize>(),
+            json.len()
+        );
+        assert_eq!(stats.kinds[0], KindStats { count: 1, bytes: 5 });
+        assert_eq!(stats.kinds[2], KindStats { count: 2, bytes: 3 });
+        assert_eq!(stats.kinds[4].count, 3);
+        assert_eq!(stats.kinds[5].count, 2);
+        assert_eq!(stats.max_depth.value, 3);
+        assert_eq!(stats.max_depth.path, vec![0, 2, 0]);
+        assert_eq!(
+            stats.largest_array,
+            Some(Extreme {
+                value: 3,
+                path: vec![0]
+            })
+        );
+        assert_eq!(
+            stats.longest_string,
+            Some(Extreme {
+                value: 11,
+                path: vec![1, 0, 0]
+            })
+        );
+    }
+
+    #[test]
+    fn test_scalar_stats() {
+        let json = r#""""#;
+        let stats = Stats::new(&JsonSize::new(json));
+        assert_eq!(stats.nodes, 1);
+        assert_eq!(stats.max_depth, Extreme::default());
+        assert_eq!(stats.largest_array, None);
+        assert_eq!(stats.longest_string, Some(Extreme::default()));
+    }
+}
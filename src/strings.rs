@@ -0,0 +1,365 @@
+//! Report repeated string values, and estimate how much smaller the file would be if the strings
+//! of each generalized path were replaced by integer codes into a dictionary.
+use crate::size::{canonical_json_string, percent_of, size_bar, JsonSize, JsonValueKind};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StringValueStats {
+    /// Number of times the value appears
+    pub count: usize,
+    /// Bytes of all the occurrences, including quotes
+    pub bytes: usize,
+}
+
+/// String values found at one generalized path
+#[derive(Debug, Default, Clone)]
+pub struct PathStrings {
+    /// Number of string values
+    pub count: usize,
+    /// Bytes of all the string values, including quotes
+    pub bytes: usize,
+    /// Hashes of the distinct values, at most `StringStats::max_values`
+    hashes: HashSet<u64>,
+    /// Number of distinct values. Once `hashes` is full, every value that is not in it counts
+    /// as new, so this is an upper bound.
+    distinct: usize,
+    /// Bytes of the distinct values, including quotes
+    distinct_bytes: usize,
+    /// `hashes` is full, `distinct` may be too high
+    pub approximate: bool,
+}
+
+impl PathStrings {
+    pub fn distinct(&self) -> usize {
+        self.distinct
+    }
+
+    /// Bytes needed to store the strings as integer codes, plus a dictionary with every distinct
+    /// value once: an array of strings, so each value needs an extra comma.
+    pub fn encoded_size(&self) -> usize {
+        let code_len = (self.distinct().max(1) - 1).checked_ilog10().unwrap_or(0) as usize + 1;
+        self.count * code_len + self.distinct_bytes + self.distinct()
+    }
+
+    /// Bytes saved by dictionary encoding, 0 if it would make the file bigger
+    pub fn savings(&self) -> usize {
+        self.bytes.saturating_sub(self.encoded_size())
+    }
+}
+
+/// Distinct values kept by `StringStats::new`
+const MAX_VALUES: usize = 100_000;
+
+/// Accumulates string value statistics, by value and by generalized path (see `schema`). Can be
+/// fed more than one tree.
+#[derive(Debug)]
+pub struct StringStats {
+    /// Indexed by the string with quotes and only the required escape sequences, so `"\u0041"`
+    /// and `"A"` are the same value
+    values: HashMap<String, StringValueStats>,
+    /// When `values` has twice as many, only this many with the most bytes are kept. Also the
+    /// number of hashes kept by each path.
+    max_values: usize,
+    /// Some values were dropped, the counts of the others may be too low
+    pruned: bool,
+    paths: HashMap<String, PathStrings>,
+    /// Sum of the sizes of all the trees
+    total: usize,
+}
+
+impl Default for StringStats {
+    fn default() -> Self {
+        Self::with_max_values(MAX_VALUES)
+    }
+}
+
+impl StringStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep at most about `max_values` distinct values, so that memory does not grow with the
+    /// input. The values with the most bytes so far are kept, and each path keeps the hashes of
+    /// `max_values` values.
+    pub fn with_max_values(max_values: usize) -> Self {
+        StringStats {
+            values: HashMap::new(),
+            max_values,
+            pruned: false,
+            paths: HashMap::new(),
+            total: 0,
+        }
+    }
+
+    pub fn add(&mut self, js: &JsonSize, json: &str) {
+        self.total += js.total_size();
+        let mut path = String::new();
+        self.add_value(js, json, &mut path);
+    }
+
+    fn add_value(&mut self, js: &JsonSize, json: &str, path: &mut String) {
+        if js.value_kind() == JsonValueKind::String {
+            let raw = &json[js.span()];
+            let value = canonical_json_string(raw);
+            let stats = match self.values.get_mut(value.as_ref()) {
+                Some(stats) => stats,
+                None => self.values.entry(value.to_string()).or_default(),
+            };
+            stats.count += 1;
+            stats.bytes += raw.len();
+            if self.values.len() > 2 * self.max_values {
+                self.prune_values();
+            }
+
+            let stats = match self.paths.get_mut(path.as_str()) {
+                Some(stats) => stats,
+                None => self.paths.entry(path.clone()).or_default(),
+            };
+            stats.count += 1;
+            stats.bytes += raw.len();
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            let hash = hasher.finish();
+            if !stats.hashes.contains(&hash) {
+                if stats.hashes.len() < self.max_values {
+                    stats.hashes.insert(hash);
+                } else {
+                    stats.approximate = true;
+                }
+                stats.distinct += 1;
+                stats.distinct_bytes += value.len();
+            }
+            return;
+        }
+
+        let path_len = path.len();
+        for child in js.children() {
+            path.push('/');
+            match js.value_kind() {
+                JsonValueKind::Array => path.push('*'),
+                _ => path.push_str(&child.key().to_json_pointer_token(json)),
+            }
+            self.add_value(child, json, path);
+            path.truncate(path_len);
+        }
+    }
+
+    fn prune_values(&mut self) {
+        let mut values: Vec<_> = self.values.drain().collect();
+        values.select_nth_unstable_by(self.max_values, |a, b| b.1.bytes.cmp(&a.1.bytes));
+        values.truncate(self.max_values);
+        self.values = values.into_iter().collect();
+        self.pruned = true;
+    }
+
+    /// All the distinct string values, most bytes first
+    pub fn sorted_values(&self) -> Vec<(&str, &StringValueStats)> {
+        let mut values: Vec<_> = self
+            .values
+            .iter()
+            .map(|(value, stats)| (value.as_str(), stats))
+            .collect();
+        values.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then_with(|| a.0.cmp(b.0)));
+        values
+    }
+
+    /// All the generalized paths with string values, most savings first
+    pub fn sorted_paths(&self) -> Vec<(&str, &PathStrings)> {
+        let mut paths: Vec<_> = self
+            .paths
+            .iter()
+            .map(|(path, stats)| (path.as_str(), stats))
+            .collect();
+        paths.sort_by(|a, b| {
+            b.1.savings()
+                .cmp(&a.1.savings())
+                .then_with(|| b.1.bytes.cmp(&a.1.bytes))
+                .then_with(|| a.0.cmp(b.0))
+        });
+        paths
+    }
+
+    /// Format as two tables: the most frequent values and the paths that would benefit the most
+    /// from dictionary encoding, showing at most `limit` rows each
+    pub fn display_list(&self, limit: Option<usize>) -> Vec<String> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut lines = vec![format!("{:>12} {:12} {:>10} Value", "Bytes", "", "Count")];
+        for (value, stats) in self.sorted_values().into_iter().take(limit) {
+            lines.push(format!(
+                "{:12} {} {:10} {}",
+                stats.bytes,
+                size_bar(stats.bytes, self.total),
+                stats.count,
+                value
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(format!(
+            "{:>12} {:12} {:>10} {:>10} {:>12} Path",
+            "Savings", "", "Count", "Distinct", "Bytes"
+        ));
+        let sorted = self.sorted_paths();
+        for (path, stats) in sorted.iter().take(limit) {
+            // At most this many
+            let distinct = if stats.approximate {
+                format!("~{}", stats.distinct())
+            } else {
+                stats.distinct().to_string()
+            };
+            lines.push(format!(
+                "{:12} {} {:10} {:>10} {:12} {}",
+                stats.savings(),
+                size_bar(stats.savings(), self.total),
+                stats.count,
+                distinct,
+                stats.bytes,
+                path
+            ));
+        }
+
+        let savings: usize = sorted.iter().map(|(_, stats)| stats.savings()).sum();
+        let distinct = if self.pruned {
+            format!(
+                "over {} distinct values (approximate counts)",
+                2 * self.max_values
+            )
+        } else {
+            format!("{} distinct values", self.values.len())
+        };
+        lines.push(format!(
+            "{:12} {} Total: {}, dictionary encoding saves {:.1}% of {} bytes",
+            savings,
+            size_bar(savings, self.total),
+            distinct,
+            percent_of(savings, self.total),
+            self.total
+        ));
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_values() {
+        let json = r#"[{"status": "ACTIVE", "id": "a"}, {"status": "ACTIVE", "id": "b"}, "a"]"#;
+        let js = JsonSize::new(json);
+        let mut strings = StringStats::new();
+        strings.add(&js, json);
+
+        let values = strings.sorted_values();
+        assert_eq!(values.len(), 3);
+        assert_eq!(
+            values[0],
+            (
+                r#""ACTIVE""#,
+                &StringValueStats {
+                    count: 2,
+                    bytes: 16
+                }
+            )
+        );
+        assert_eq!(
+            values[1],
+            (r#""a""#, &StringValueStats { count: 2, bytes: 6 })
+        );
+    }
+
+    #[test]
+    fn test_dictionary_savings() {
+        let json = r#"[{"s": "ACTIVE"}, {"s": "ACTIVE"}, {"s": "ACTIVE"}, {"s": "DELETED"}]"#;
+        let js = JsonSize::new(json);
+        let mut strings = StringStats::new();
+        strings.add(&js, json);
+
+        let paths = strings.sorted_paths();
+        assert_eq!(paths.len(), 1);
+        let (path, stats) = paths[0];
+        assert_eq!(path, "/*/s");
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.distinct(), 2);
+        assert_eq!(stats.bytes, 3 * 8 + 9);
+        // 4 one-digit codes, and ["ACTIVE","DELETED"] without brackets
+        assert_eq!(stats.encoded_size(), 4 + 8 + 9 + 2);
+        assert_eq!(stats.savings(), 33 - 23);
+    }
+
+    #[test]
+    fn test_unique_values_have_no_savings() {
+        let json = r#"["a", "b", "c"]"#;
+        let mut strings = StringStats::new();
+        strings.add(&JsonSize::new(json), json);
+        assert_eq!(strings.sorted_paths()[0].1.savings(), 0);
+    }
+
+    #[test]
+    fn test_escapes_are_decoded() {
+        let json = r#"["\u0041", "A", "a\/b", "a/b"]"#;
+        let mut strings = StringStats::new();
+        strings.add(&JsonSize::new(json), json);
+
+        let values = strings.sorted_values();
+        assert_eq!(values.len(), 2);
+        assert_eq!(
+            values[0],
+            (
+                r#""A""#,
+                &StringValueStats {
+                    count: 2,
+                    bytes: 11
+                }
+            )
+        );
+        assert_eq!(
+            values[1],
+            (
+                r#""a/b""#,
+                &StringValueStats {
+                    count: 2,
+                    bytes: 11
+                }
+            )
+        );
+        assert_eq!(strings.sorted_paths()[0].1.distinct(), 2);
+    }
+
+    #[test]
+    fn test_max_values() {
+        let json = r#"["aaaa", "b", "aaaa", "c", "d", "e", "b"]"#;
+        let mut strings = StringStats::with_max_values(1);
+        strings.add(&JsonSize::new(json), json);
+
+        // Pruned after "c" and after "e", the second "b" starts again from 0
+        let values = strings.sorted_values();
+        assert_eq!(values.len(), 2);
+        assert_eq!(
+            values[0],
+            (
+                r#""aaaa""#,
+                &StringValueStats {
+                    count: 2,
+                    bytes: 12
+                }
+            )
+        );
+        assert_eq!(
+            values[1],
+            (r#""b""#, &StringValueStats { count: 1, bytes: 3 })
+        );
+        // Only the hash of "aaaa" is kept, every other value counts as new
+        let (_, path) = strings.sorted_paths()[0];
+        assert!(path.approximate);
+        assert_eq!(path.distinct(), 6);
+        assert!(strings
+            .display_list(None)
+            .last()
+            .unwrap()
+            .contains("Total: over 2 distinct values (approximate counts)"));
+    }
+}
@@ -0,0 +1,142 @@
+//! Find the biggest values anywhere in the tree, no matter how deep they are.
+use crate::size::{size_bar, JsonSize};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// The `n` largest values without children (strings, numbers, keywords and empty containers),
+/// biggest first. Returns their size and path of child indices.
+pub fn largest_leaves(root: &JsonSize, n: usize) -> Vec<(usize, Vec<usize>)> {
+    // Min-heap with the n largest leaves found so far
+    let mut heap = BinaryHeap::new();
+    let mut stack = vec![(vec![], root)];
+    while let Some((path, node)) = stack.pop() {
+        if node.children().is_empty() {
+            heap.push(Reverse((node.total_size(), path)));
+            if heap.len() > n {
+                heap.pop();
+            }
+            continue;
+        }
+        for (i, child) in node.children().iter().enumerate().rev() {
+            let mut child_path = path.clone();
+            child_path.push(i);
+            stack.push((child_path, child));
+        }
+    }
+
+    sorted_desc(heap.into_iter().map(|Reverse(x)| x).collect())
+}
+
+/// The `n` largest objects and arrays such that none of them contains another one.
+/// Starting from the root, the largest container is repeatedly replaced by the containers inside
+/// it, until there are `n` of them. Containers are not split when less than half of their size
+/// is in other containers, because most of their bytes would stop being reported.
+pub fn largest_containers(root: &JsonSize, n: usize) -> Vec<(usize, Vec<usize>)> {
+    let mut splittable = BinaryHeap::new();
+    let mut unsplittable = vec![];
+    if !root.children().is_empty() {
+        splittable.push((root.total_size(), vec![]));
+    }
+
+    while splittable.len() + unsplittable.len() < n {
+        let (size, path) = match splittable.pop() {
+            Some(x) => x,
+            None => break,
+        };
+        let node = root.get_path(&path).unwrap();
+        let containers_size: usize = node
+            .children()
+            .iter()
+            .filter(|child| !child.children().is_empty())
+            .map(|child| child.total_size())
+            .sum();
+        if containers_size * 2 < size {
+            unsplittable.push((size, path));
+            continue;
+        }
+        for (i, child) in node.children().iter().enumerate() {
+            if !child.children().is_empty() {
+                let mut child_path = path.clone();
+                child_path.push(i);
+                splittable.push((child.total_size(), child_path));
+            }
+        }
+    }
+
+    let mut containers = sorted_desc(splittable.into_iter().chain(unsplittable).collect());
+    containers.truncate(n);
+    containers
+}
+
+fn sorted_desc(mut x: Vec<(usize, Vec<usize>)>) -> Vec<(usize, Vec<usize>)> {
+    // Biggest first, and in document order on ties
+    x.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    x
+}
+
+/// Format like `display_list`, using the JSON pointer of each value as the name
+pub fn display_list(root: &JsonSize, json: &str, values: &[(usize, Vec<usize>)]) -> Vec<String> {
+    values
+        .iter()
+        .map(|(size, path)| {
+            format!(
+                "{:12} {} {}",
+                size,
+                size_bar(*size, root.total_size()),
+                root.path_to_json_pointer(json, path).unwrap()
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pointers(json: &str, values: &[(usize, Vec<usize>)]) -> Vec<(usize, String)> {
+        let js = JsonSize::new(json);
+        values
+            .iter()
+            .map(|(size, path)| (*size, js.path_to_json_pointer(json, path).unwrap()))
+            .collect()
+    }
+
+    const JSON: &str = r#"{"a":{"b":{"c":"xxxxxxxxxxxxxxxxxxxx","d":[1,2]}},"e":[{"f":"yyyyyyyy"},{"g":"zzzz"}],"h":[]}"#;
+
+    #[test]
+    fn test_largest_leaves() {
+        let js = JsonSize::new(JSON);
+        assert_eq!(
+            pointers(JSON, &largest_leaves(&js, 3)),
+            vec![
+                (22, "/a/b/c".to_string()),
+                (10, "/e/0/f".to_string()),
+                (6, "/e/1/g".to_string()),
+            ]
+        );
+        assert_eq!(largest_leaves(&js, 100).len(), 6);
+    }
+
+    #[test]
+    fn test_largest_containers() {
+        let js = JsonSize::new(JSON);
+        assert_eq!(
+            pointers(JSON, &largest_containers(&js, 1)),
+            vec![(JSON.len(), "".to_string())]
+        );
+        assert_eq!(
+            pointers(JSON, &largest_containers(&js, 2)),
+            vec![(44, "/a".to_string()), (31, "/e".to_string())]
+        );
+        // Most of /a/b is a string, so it is not split into /a/b/d
+        assert_eq!(
+            pointers(JSON, &largest_containers(&js, 3)),
+            vec![
+                (38, "/a/b".to_string()),
+                (16, "/e/0".to_string()),
+                (12, "/e/1".to_string())
+            ]
+        );
+        assert_eq!(largest_containers(&js, 100).len(), 3);
+    }
+}
@@ -0,0 +1,1088 @@
+//! YAML frontend: convert a YAML file to JSON, so that every command can explore it, while the
+//! sizes are measured in bytes of the YAML file. Indentation and line breaks count as
+//! whitespace, indicators like `-`, `:`, quotes, anchors and tags count as control characters,
+//! and the text of scalars counts as data.
+//!
+//! A file with several documents is converted to an array of documents. Aliases are not
+//! expanded, they become the string `"*name"`, and anchors are reported separately with the size
+//! of their value and the number of aliases that reference them.
+use crate::encoding::{Decoded, Encoding};
+use crate::pointer::Location;
+use crate::size::{push_json_string, ByteCounts, JsonSize, SourceSize};
+use std::collections::HashMap;
+use std::path::Path;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser, Tag};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
+
+/// A YAML file converted to JSON
+#[derive(Debug)]
+pub struct YamlFile {
+    pub json: String,
+    /// Structure of `json`, with the sizes in bytes of the YAML file
+    pub js: JsonSize,
+    /// In order of definition
+    pub anchors: Vec<Anchor>,
+    /// Positions of the values in the YAML file
+    sizes: SourceSize,
+}
+
+/// A value with an anchor, like `base: &base {...}`
+#[derive(Debug, PartialEq, Eq)]
+pub struct Anchor {
+    pub name: String,
+    /// JSON pointer of the value
+    pub pointer: String,
+    /// Size of the definition in the YAML file
+    pub size: usize,
+    /// Number of aliases that reference this anchor
+    pub references: usize,
+}
+
+impl YamlFile {
+    /// Find the innermost value whose text contains `offset`, a byte offset of the YAML file.
+    /// Offsets in indicators like `-` belong to the enclosing collection, and offsets in a key
+    /// to its member.
+    pub fn locate(&self, offset: usize) -> Location {
+        let mut path = vec![];
+        let (mut js, mut sizes) = (&self.js, &self.sizes);
+        loop {
+            let children = &sizes.children;
+            let i = children.partition_point(|child| child.member_span.start <= offset);
+            let i = match i.checked_sub(1) {
+                Some(i) => i,
+                None => break,
+            };
+            let (child_js, child) = (&js.children()[i], &children[i]);
+            if child.value_span.contains(&offset) {
+                path.push(i);
+                (js, sizes) = (child_js, child);
+                continue;
+            }
+            if child.member_span.contains(&offset) && child_js.key().key_span().is_some() {
+                path.push(i);
+                return Location { path, in_key: true };
+            }
+            break;
+        }
+
+        Location {
+            path,
+            in_key: false,
+        }
+    }
+}
+
+/// Files with a `.yaml` or `.yml` extension, ignoring a compression extension
+pub fn is_yaml_path(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    matches!(
+        crate::files::extension_without_compression(&name),
+        Some("yaml" | "yml")
+    )
+}
+
+struct Node {
+    kind: NodeKind,
+    /// Anchor id assigned by the parser, 0 if the node has no anchor
+    anchor: usize,
+    /// Byte offset of the content of scalars and aliases
+    mark: usize,
+}
+
+enum NodeKind {
+    Scalar {
+        value: String,
+        style: TScalarStyle,
+        tag: Option<Tag>,
+    },
+    /// Id of the anchor that the alias references
+    Alias(usize),
+    Sequence(Vec<Node>),
+    Mapping(Vec<(Node, Node)>),
+}
+
+#[derive(Default)]
+struct Events(Vec<(Event, Marker)>);
+
+impl MarkedEventReceiver for Events {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        self.0.push((ev, mark));
+    }
+}
+
+/// Build the node that starts with `ev`, consuming the events of its children
+fn read_node(
+    ev: Event,
+    mark: usize,
+    events: &mut impl Iterator<Item = (Event, usize)>,
+) -> Option<Node> {
+    let (kind, anchor) = match ev {
+        Event::Scalar(value, style, anchor, tag) => {
+            (NodeKind::Scalar { value, style, tag }, anchor)
+        }
+        Event::Alias(id) => (NodeKind::Alias(id), 0),
+        Event::SequenceStart(anchor, _) => {
+            let mut items = vec![];
+            loop {
+                match events.next()? {
+                    (Event::SequenceEnd, _) => break,
+                    (ev, mark) => items.push(read_node(ev, mark, events)?),
+                }
+            }
+            (NodeKind::Sequence(items), anchor)
+        }
+        Event::MappingStart(anchor, _) => {
+            let mut pairs = vec![];
+            loop {
+                let key = match events.next()? {
+                    (Event::MappingEnd, _) => break,
+                    (ev, mark) => read_node(ev, mark, events)?,
+                };
+                let (ev, mark) = events.next()?;
+                pairs.push((key, read_node(ev, mark, events)?));
+            }
+            (NodeKind::Mapping(pairs), anchor)
+        }
+        _ => return None,
+    };
+
+    Some(Node { kind, anchor, mark })
+}
+
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+enum Class {
+    #[default]
+    Unknown,
+    Whitespace,
+    Comment,
+    Data,
+    Quote,
+    /// Flow commas and the `-` of block sequences
+    Comma,
+    /// `:` and `?`
+    Colon,
+    Bracket,
+    Other,
+}
+
+impl Class {
+    fn is_control(self) -> bool {
+        matches!(
+            self,
+            Class::Quote | Class::Comma | Class::Colon | Class::Bracket | Class::Other
+        )
+    }
+}
+
+/// Positions of a value in the YAML file
+struct Layout {
+    /// End of the text of the value. The whitespace and comments after it are part of the
+    /// value, and any other character, like a flow comma, is part of the member.
+    end: usize,
+    /// Block collections extend until the next member, their children cover the rest
+    block: bool,
+    children: Vec<MemberLayout>,
+}
+
+struct MemberLayout {
+    /// Start of the key, or of the `-` of block sequence items
+    start: usize,
+    /// Start of the value, including the whitespace before it
+    value_start: usize,
+    value: Layout,
+}
+
+fn is_blank(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\r' | b'\n')
+}
+
+struct Source<'a> {
+    bytes: &'a [u8],
+    classes: Vec<Class>,
+    /// Size in the encoding of the file of each byte of the text, if it is not UTF-8: the size of
+    /// the char on its first byte and 0 on the others
+    weights: Option<Vec<u8>>,
+    /// Names of the anchors by id
+    anchors: HashMap<usize, String>,
+}
+
+impl<'a> Source<'a> {
+    fn new(yaml: &'a str, encoding: Encoding) -> Self {
+        let weights = (encoding != Encoding::Utf8).then(|| {
+            let mut weights = vec![0; yaml.len()];
+            for (i, c) in yaml.char_indices() {
+                weights[i] = encoding.char_len(c) as u8;
+            }
+            weights
+        });
+        Source {
+            bytes: yaml.as_bytes(),
+            classes: vec![Class::Unknown; yaml.len()],
+            weights,
+            anchors: HashMap::new(),
+        }
+    }
+
+    fn byte(&self, pos: usize) -> Option<u8> {
+        self.bytes.get(pos).copied()
+    }
+
+    fn mark(&mut self, range: std::ops::Range<usize>, class: Class) {
+        self.classes[range].fill(class);
+    }
+
+    /// Skip whitespace and comments
+    fn next_token(&self, mut pos: usize) -> usize {
+        while let Some(b) = self.byte(pos) {
+            if is_blank(b) {
+                pos += 1;
+            } else if b == b'#' && (pos == 0 || is_blank(self.bytes[pos - 1])) {
+                while self.byte(pos).is_some_and(|b| b != b'\n') {
+                    pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+
+        pos
+    }
+
+    /// End of an anchor, alias or tag
+    fn token_end(&self, mut pos: usize) -> usize {
+        while self
+            .byte(pos)
+            .is_some_and(|b| !is_blank(b) && !b",[]{}".contains(&b))
+        {
+            pos += 1;
+        }
+
+        pos
+    }
+
+    /// Skip the anchor and tag of a node
+    fn skip_properties(&self, mut pos: usize) -> usize {
+        loop {
+            let next = self.next_token(pos);
+            match self.byte(next) {
+                Some(b'&' | b'!') => pos = self.token_end(next),
+                _ => return pos,
+            }
+        }
+    }
+
+    /// Name of the anchor among the properties that start at `pos`
+    fn anchor_name(&self, mut pos: usize) -> Option<String> {
+        loop {
+            let next = self.next_token(pos);
+            pos = self.token_end(next);
+            match self.byte(next) {
+                Some(b'&') => {
+                    let name = &self.bytes[next + 1..pos];
+                    return Some(String::from_utf8_lossy(name).into_owned());
+                }
+                Some(b'!') => continue,
+                _ => return None,
+            }
+        }
+    }
+
+    /// Skip directives and `---` and `...` markers
+    fn skip_document_markers(&self, mut pos: usize) -> usize {
+        loop {
+            let next = self.next_token(pos);
+            let rest = &self.bytes[next..];
+            let is_marker = (rest.starts_with(b"---") || rest.starts_with(b"..."))
+                && rest.get(3).is_none_or(|&b| is_blank(b));
+            if is_marker {
+                pos = next + 3;
+            } else if rest.starts_with(b"%") {
+                pos = next + rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            } else {
+                return pos;
+            }
+        }
+    }
+
+    /// Position after the comma that follows a flow item, if any
+    fn skip_comma(&self, pos: usize) -> usize {
+        let next = self.next_token(pos);
+        if self.byte(next) == Some(b',') {
+            next + 1
+        } else {
+            pos
+        }
+    }
+
+    /// Mark the text of a quoted scalar that starts at `start`, returns its end
+    fn quoted_scalar(&mut self, start: usize) -> usize {
+        let quote = self.bytes[start];
+        let mut pos = start + 1;
+        while let Some(b) = self.byte(pos) {
+            // A backslash escape, or '' inside single quotes
+            let escaped = if quote == b'"' {
+                b == b'\\'
+            } else {
+                b == quote && self.byte(pos + 1) == Some(b'\'')
+            };
+            if escaped {
+                pos += 2;
+            } else if b == quote {
+                break;
+            } else {
+                pos += 1;
+            }
+        }
+        let close = pos.min(self.bytes.len());
+        self.mark(start + 1..close, Class::Data);
+        self.mark(start..start + 1, Class::Quote);
+        if close < self.bytes.len() {
+            self.mark(close..close + 1, Class::Quote);
+        }
+
+        (close + 1).min(self.bytes.len())
+    }
+
+    /// Mark the text of a plain scalar that starts at `start`, returns its end. Line breaks of
+    /// multi-line scalars are folded into spaces in `value`.
+    fn plain_scalar(&mut self, start: usize, value: &str) -> usize {
+        let mut pos = start;
+        let mut value = value.bytes().peekable();
+        while let Some(b) = value.next() {
+            if is_blank(b) {
+                while value.next_if(|&b| is_blank(b)).is_some() {}
+                while self.byte(pos).is_some_and(is_blank) {
+                    pos += 1;
+                }
+            } else if self.byte(pos) == Some(b) {
+                pos += 1;
+            } else {
+                break;
+            }
+        }
+        self.mark(start..pos, Class::Data);
+
+        pos
+    }
+
+    /// Mark the lines of a literal or folded block scalar whose first line of content starts at
+    /// `start`, returns the end of the last line. The indentation and line breaks are whitespace.
+    fn block_scalar(&mut self, start: usize) -> usize {
+        let line_start = self.bytes[..start]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |x| x + 1);
+        let indent = start - line_start;
+        let mut pos = line_start;
+        let mut end = start;
+        while pos < self.bytes.len() {
+            let line_end = self.bytes[pos..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(self.bytes.len(), |x| pos + x);
+            let line = &self.bytes[pos..line_end];
+            let spaces = line.iter().take_while(|&&b| b == b' ').count();
+            let blank = line[spaces..].iter().all(|&b| is_blank(b));
+            let is_marker = line.starts_with(b"---") || line.starts_with(b"...");
+            if !blank && (spaces < indent || (indent == 0 && is_marker)) {
+                break;
+            }
+            if !blank {
+                let text_end = line_end - usize::from(line.ends_with(b"\r"));
+                self.mark(pos + indent..text_end, Class::Data);
+                end = text_end;
+            }
+            pos = line_end + 1;
+        }
+
+        end
+    }
+
+    /// Find the positions of `node`, that starts after `start`, and mark the text of its
+    /// scalars
+    fn layout(&mut self, node: &Node, start: usize) -> Layout {
+        if node.anchor != 0 {
+            if let Some(name) = self.anchor_name(start) {
+                self.anchors.insert(node.anchor, name);
+            }
+        }
+        let scalar = |end| Layout {
+            end,
+            block: false,
+            children: vec![],
+        };
+
+        match &node.kind {
+            NodeKind::Scalar { value, style, .. } => {
+                let quoted = matches!(
+                    style,
+                    TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted
+                );
+                if value.is_empty() && !quoted {
+                    // Empty values have no text, only properties and maybe an empty block
+                    // scalar header
+                    let end = self.skip_properties(start);
+                    let next = self.next_token(end);
+                    return match self.byte(next) {
+                        Some(b'|' | b'>') if style != &TScalarStyle::Plain => {
+                            scalar(self.token_end(next))
+                        }
+                        _ => scalar(end),
+                    };
+                }
+                scalar(match style {
+                    TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted => {
+                        self.quoted_scalar(node.mark)
+                    }
+                    TScalarStyle::Literal | TScalarStyle::Folded => self.block_scalar(node.mark),
+                    _ => self.plain_scalar(node.mark, value),
+                })
+            }
+            NodeKind::Alias(_) => scalar(self.token_end(node.mark + 1)),
+            NodeKind::Sequence(items) => {
+                let content = self.next_token(self.skip_properties(start));
+                if self.byte(content) == Some(b'[') {
+                    let mut pos = content + 1;
+                    let children = items
+                        .iter()
+                        .map(|item| {
+                            let value = self.layout(item, pos);
+                            let member = MemberLayout {
+                                start: pos,
+                                value_start: pos,
+                                value,
+                            };
+                            pos = self.skip_comma(member.value.end);
+                            member
+                        })
+                        .collect();
+                    self.flow_end(pos, children)
+                } else {
+                    let mut end = content;
+                    let children = items
+                        .iter()
+                        .enumerate()
+                        .map(|(i, item)| {
+                            let dash = if i == 0 {
+                                content
+                            } else {
+                                self.next_token(end)
+                            };
+                            let value = self.layout(item, dash + 1);
+                            end = value.end;
+                            MemberLayout {
+                                start: dash,
+                                value_start: dash + 1,
+                                value,
+                            }
+                        })
+                        .collect();
+                    Layout {
+                        end,
+                        block: true,
+                        children,
+                    }
+                }
+            }
+            NodeKind::Mapping(pairs) => {
+                let content = self.next_token(self.skip_properties(start));
+                let flow = self.byte(content) == Some(b'{');
+                let mut pos = if flow { content + 1 } else { content };
+                let mut end = content;
+                let children = pairs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (key, value))| {
+                        let member_start = if flow || i == 0 {
+                            pos
+                        } else {
+                            self.next_token(end)
+                        };
+                        let mut key_start = member_start;
+                        let next = self.next_token(key_start);
+                        if self.byte(next) == Some(b'?') && self.byte(next + 1).is_none_or(is_blank)
+                        {
+                            key_start = next + 1;
+                        }
+                        let key = self.layout(key, key_start);
+                        let colon = self.next_token(key.end);
+                        let value_start = if self.byte(colon) == Some(b':') {
+                            colon + 1
+                        } else {
+                            key.end
+                        };
+                        let value = self.layout(value, value_start);
+                        end = value.end;
+                        pos = self.skip_comma(end);
+                        MemberLayout {
+                            start: member_start,
+                            value_start,
+                            value,
+                        }
+                    })
+                    .collect();
+                if flow {
+                    self.flow_end(pos, children)
+                } else {
+                    Layout {
+                        end,
+                        block: true,
+                        children,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Layout of a flow collection, `pos` is after its last item
+    fn flow_end(&self, pos: usize, children: Vec<MemberLayout>) -> Layout {
+        let close = self.next_token(pos);
+        Layout {
+            end: (close + 1).min(self.bytes.len()),
+            block: false,
+            children,
+        }
+    }
+
+    /// Classify the characters that are not part of a scalar
+    fn classify_rest(&mut self) {
+        let mut pos = 0;
+        while pos < self.bytes.len() {
+            if self.classes[pos] != Class::Unknown {
+                pos += 1;
+                continue;
+            }
+            let after_blank = pos == 0 || is_blank(self.bytes[pos - 1]);
+            let class = match self.bytes[pos] {
+                b if is_blank(b) => Class::Whitespace,
+                b'#' if after_blank => {
+                    while self.byte(pos).is_some_and(|b| b != b'\n') {
+                        self.classes[pos] = Class::Comment;
+                        pos += 1;
+                    }
+                    continue;
+                }
+                b'-' if after_blank && self.byte(pos + 1).is_none_or(is_blank) => Class::Comma,
+                b',' => Class::Comma,
+                b':' | b'?' => Class::Colon,
+                b'[' | b']' | b'{' | b'}' => Class::Bracket,
+                _ => Class::Other,
+            };
+            self.classes[pos] = class;
+            pos += 1;
+        }
+    }
+
+    /// Count the byte at `pos`, in the encoding of the file
+    fn add(&self, counts: &mut ByteCounts, pos: usize) {
+        let n = self
+            .weights
+            .as_ref()
+            .map_or(1, |weights| weights[pos] as usize);
+        match self.classes[pos] {
+            Class::Unknown | Class::Whitespace => counts.whitespace += n,
+            Class::Comment => counts.comments += n,
+            Class::Data => counts.data_size += n,
+            Class::Quote => counts.control.quotes += n,
+            Class::Comma => counts.control.commas += n,
+            Class::Colon => counts.control.colons += n,
+            Class::Bracket => counts.control.brackets += n,
+            Class::Other => counts.control.other += n,
+        }
+    }
+
+    fn count(&self, start: usize, end: usize) -> ByteCounts {
+        let mut counts = ByteCounts::default();
+        for pos in start..end.max(start) {
+            self.add(&mut counts, pos);
+        }
+
+        counts
+    }
+
+    /// Sizes of a value that covers `start..end`, and the control characters after its text
+    /// that belong to the member
+    fn measure(&self, layout: &Layout, start: usize, end: usize) -> (SourceSize, ByteCounts) {
+        let text_end = layout.end.clamp(start, end);
+        // Flow collections end with a bracket
+        let children_end = if layout.block {
+            end
+        } else {
+            text_end.saturating_sub(1).max(start)
+        };
+        let mut size = SourceSize::default();
+        let mut pos = start;
+        for (i, member) in layout.children.iter().enumerate() {
+            let member_start = member.start.clamp(pos, children_end);
+            let next = layout
+                .children
+                .get(i + 1)
+                .map_or(children_end, |x| x.start.clamp(member_start, children_end));
+            let value_start = member.value_start.clamp(member_start, next);
+            size.value.add(&self.count(pos, member_start));
+            let (mut child, trailing) = self.measure(&member.value, value_start, next);
+            child.member = self.count(member_start, value_start);
+            child.member.add(&trailing);
+            size.value.add(&child.value);
+            size.value.add(&child.member);
+            child.member_span = member_start..next;
+            size.children.push(child);
+            pos = next;
+        }
+
+        size.value_span = start..if layout.block { end } else { text_end };
+        let mut trailing = ByteCounts::default();
+        if layout.block {
+            size.value.add(&self.count(pos, end));
+        } else {
+            size.value.add(&self.count(pos, text_end));
+            for pos in text_end..end {
+                if self.classes[pos].is_control() {
+                    self.add(&mut trailing, pos);
+                } else {
+                    self.add(&mut size.value, pos);
+                }
+            }
+        }
+
+        (size, trailing)
+    }
+
+    /// Sizes of the whole file. Several documents are items of an array.
+    fn measure_documents(&mut self, documents: &[Node]) -> SourceSize {
+        let mut layouts = vec![];
+        let mut end = 0;
+        for (i, document) in documents.iter().enumerate() {
+            let start = if i == 0 { 0 } else { self.next_token(end) };
+            let value_start = self.skip_document_markers(start);
+            let layout = self.layout(document, value_start);
+            end = layout.end;
+            layouts.push((start, value_start, layout));
+        }
+        self.classify_rest();
+
+        let len = self.bytes.len();
+        if let [(_, _, layout)] = &layouts[..] {
+            let (mut size, trailing) = self.measure(layout, 0, len);
+            size.value.add(&trailing);
+            return size;
+        }
+        let mut root = SourceSize::default();
+        for (i, (start, value_start, layout)) in layouts.iter().enumerate() {
+            let next = layouts.get(i + 1).map_or(len, |x| x.0);
+            let (mut document, trailing) = self.measure(layout, *value_start, next);
+            document.member = self.count(*start, *value_start);
+            document.member.add(&trailing);
+            document.member_span = *start..next;
+            root.value.add(&document.value);
+            root.value.add(&document.member);
+            root.children.push(document);
+        }
+        root.value_span = 0..len;
+
+        root
+    }
+
+    fn alias_name(&self, node: &Node) -> String {
+        let end = self.token_end(node.mark + 1);
+        String::from_utf8_lossy(&self.bytes[node.mark + 1..end]).into_owned()
+    }
+
+    /// Append the JSON of `node`, recording the path of each anchor and the references of each
+    /// alias
+    fn write_json(
+        &self,
+        node: &Node,
+        out: &mut String,
+        path: &mut Vec<usize>,
+        anchors: &mut AnchorPaths,
+    ) {
+        if node.anchor != 0 {
+            anchors.definitions.push((node.anchor, path.clone()));
+        }
+        match &node.kind {
+            NodeKind::Scalar { value, style, tag } => {
+                out.push_str(&scalar_to_json(value, *style, tag.as_ref()));
+            }
+            NodeKind::Alias(id) => {
+                push_json_string(out, &format!("*{}", self.alias_name(node)));
+                *anchors.references.entry(*id).or_default() += 1;
+            }
+            NodeKind::Sequence(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    path.push(i);
+                    self.write_json(item, out, path, anchors);
+                    path.pop();
+                }
+                out.push(']');
+            }
+            NodeKind::Mapping(pairs) => {
+                out.push('{');
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    push_json_string(out, &self.key_string(key, anchors));
+                    out.push(':');
+                    path.push(i);
+                    self.write_json(value, out, path, anchors);
+                    path.pop();
+                }
+                out.push('}');
+            }
+        }
+    }
+
+    /// Keys that are not strings are converted to their JSON text
+    fn key_string(&self, key: &Node, anchors: &mut AnchorPaths) -> String {
+        match &key.kind {
+            NodeKind::Scalar { value, .. } => value.clone(),
+            NodeKind::Alias(id) => {
+                *anchors.references.entry(*id).or_default() += 1;
+                format!("*{}", self.alias_name(key))
+            }
+            _ => {
+                let mut json = String::new();
+                self.write_json(key, &mut json, &mut vec![], &mut AnchorPaths::default());
+                json
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct AnchorPaths {
+    /// Anchor id and path of the value
+    definitions: Vec<(usize, Vec<usize>)>,
+    /// Number of aliases by anchor id. An anchor name can be redefined, each alias references
+    /// the last definition before it.
+    references: HashMap<usize, usize>,
+}
+
+/// JSON of a scalar, resolved with the YAML 1.2 core schema. Quoted and block scalars, and
+/// scalars with a tag that is not a core type, are strings.
+fn scalar_to_json(value: &str, style: TScalarStyle, tag: Option<&Tag>) -> String {
+    let core_type = tag
+        .filter(|tag| tag.handle == "tag:yaml.org,2002:")
+        .map(|tag| tag.suffix.as_str());
+    let resolve = match core_type {
+        Some("null" | "bool" | "int" | "float") => true,
+        Some(_) => false,
+        None => tag.is_none() && style == TScalarStyle::Plain,
+    };
+    let resolved = match value {
+        _ if !resolve => None,
+        "" | "~" | "null" | "Null" | "NULL" => Some("null".to_string()),
+        "true" | "True" | "TRUE" => Some("true".to_string()),
+        "false" | "False" | "FALSE" => Some("false".to_string()),
+        _ => json_number(value),
+    };
+
+    resolved.unwrap_or_else(|| {
+        let mut json = String::new();
+        push_json_string(&mut json, value);
+        json
+    })
+}
+
+/// Convert a YAML integer or float to a valid JSON number: `+1`, `007`, `.5`, `1.`, `0x1F`
+/// and `0o17`. Infinity and NaN have no JSON representation.
+fn json_number(s: &str) -> Option<String> {
+    let (sign, unsigned) = match s.as_bytes().first()? {
+        b'-' => ("-", &s[1..]),
+        b'+' => ("", &s[1..]),
+        _ => ("", s),
+    };
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    let radix = |digits: &str, radix| {
+        u64::from_str_radix(digits, radix)
+            .ok()
+            .map(|n| format!("{}{}", sign, n))
+    };
+    if let Some(hex) = unsigned.strip_prefix("0x") {
+        return radix(hex, 16);
+    }
+    if let Some(octal) = unsigned.strip_prefix("0o") {
+        return radix(octal, 8);
+    }
+
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(i) => (&unsigned[..i], Some(&unsigned[i + 1..])),
+        None => (unsigned, None),
+    };
+    let (int, fraction) = match mantissa.split_once('.') {
+        Some((int, fraction)) => (int, Some(fraction)),
+        None => (mantissa, None),
+    };
+    if !all_digits(int)
+        || !fraction.is_none_or(all_digits)
+        || int.is_empty() && fraction.is_none_or(str::is_empty)
+    {
+        return None;
+    }
+    let mut json = format!("{}{}", sign, int.trim_start_matches('0'));
+    if json.len() == sign.len() {
+        json.push('0');
+    }
+    if let Some(fraction) = fraction {
+        json.push('.');
+        json.push_str(if fraction.is_empty() { "0" } else { fraction });
+    }
+    if let Some(exponent) = exponent {
+        let digits = exponent.strip_prefix(['-', '+']).unwrap_or(exponent);
+        if digits.is_empty() || !all_digits(digits) {
+            return None;
+        }
+        json.push('e');
+        json.push_str(exponent);
+    }
+
+    Some(json)
+}
+
+/// Convert `yaml` to JSON and measure the size of each value in the YAML text
+pub fn parse_yaml(yaml: &str) -> Result<YamlFile, String> {
+    parse(yaml, Encoding::Utf8, false)
+}
+
+/// Like `parse_yaml`, with the sizes in bytes of the original encoding of the file. The byte
+/// order mark counts as whitespace of the root.
+pub fn parse_yaml_decoded(decoded: &Decoded) -> Result<YamlFile, String> {
+    parse(&decoded.text, decoded.encoding, decoded.bom)
+}
+
+fn parse(yaml: &str, encoding: Encoding, bom: bool) -> Result<YamlFile, String> {
+    let mut events = Events::default();
+    Parser::new_from_str(yaml)
+        .load(&mut events, true)
+        .map_err(|e| e.to_string())?;
+
+    // Markers are char offsets
+    let byte_offsets: Option<Vec<usize>> =
+        (!yaml.is_ascii()).then(|| yaml.char_indices().map(|(i, _)| i).collect());
+    let byte_offset = |mark: Marker| match &byte_offsets {
+        Some(offsets) => offsets.get(mark.index()).copied().unwrap_or(yaml.len()),
+        None => mark.index(),
+    };
+    let mut events = events
+        .0
+        .into_iter()
+        .map(|(ev, mark)| (ev, byte_offset(mark)));
+    let mut documents = vec![];
+    while let Some((ev, mark)) = events.next() {
+        match ev {
+            Event::StreamStart
+            | Event::StreamEnd
+            | Event::DocumentStart
+            | Event::DocumentEnd
+            | Event::Nothing => {}
+            ev => documents
+                .push(read_node(ev, mark, &mut events).ok_or("unexpected end of YAML document")?),
+        }
+    }
+    if documents.is_empty() {
+        return Err("no YAML document found".to_string());
+    }
+
+    let mut source = Source::new(yaml, encoding);
+    let mut sizes = source.measure_documents(&documents);
+    if bom {
+        sizes.value.whitespace += encoding.bom().len();
+    }
+    let mut json = String::new();
+    let mut anchor_paths = AnchorPaths::default();
+    if let [document] = &documents[..] {
+        source.write_json(document, &mut json, &mut vec![], &mut anchor_paths);
+    } else {
+        json.push('[');
+        for (i, document) in documents.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            source.write_json(document, &mut json, &mut vec![i], &mut anchor_paths);
+        }
+        json.push(']');
+    }
+
+    let js = JsonSize::with_source_sizes(&json, &sizes);
+    let anchors = anchor_paths
+        .definitions
+        .into_iter()
+        .map(|(id, path)| {
+            let name = source.anchors.get(&id).cloned().unwrap_or_default();
+            Anchor {
+                pointer: js.path_to_json_pointer(&json, &path).unwrap(),
+                size: js.get_path(&path).unwrap().total_size(),
+                references: anchor_paths.references.get(&id).copied().unwrap_or(0),
+                name,
+            }
+        })
+        .collect();
+
+    Ok(YamlFile {
+        json,
+        js,
+        anchors,
+        sizes,
+    })
+}
+
+/// One line per anchor, with the bytes that its aliases would use if they were expanded
+pub fn display_anchors(anchors: &[Anchor]) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{:>12} {:>8} {:>12} Anchor",
+        "Size", "Aliases", "Expanded"
+    )];
+    for anchor in anchors {
+        lines.push(format!(
+            "{:12} {:8} {:12} &{} {}",
+            anchor.size,
+            anchor.references,
+            anchor.size * anchor.references,
+            anchor.name,
+            anchor.pointer
+        ));
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::size::{Accounting, SizeBreakdown};
+
+    #[test]
+    fn test_block_yaml() {
+        let yaml = "# users\nusers:\n  - name: \"Ann\"  # admin\n    age: 30\n  - name: Bob\n    tags: [a, 'b c']\nnote: |\n  two\n  lines\n";
+        let file = parse_yaml(yaml).unwrap();
+        assert_eq!(
+            file.json,
+            r#"{"users":[{"name":"Ann","age":30},{"name":"Bob","tags":["a","b c"]}],"note":"two\nlines\n"}"#
+        );
+        assert_eq!(file.js.total_size(), yaml.len());
+
+        let path = file.js.json_pointer_path(&file.json, "/users/0").unwrap();
+        let ann = file.js.get_path(&path).unwrap();
+        // `- ` and the item, up to the `-` of the next one
+        assert_eq!(
+            ann.member_size(),
+            "- name: \"Ann\"  # admin\n    age: 30\n  ".len()
+        );
+        assert_eq!(
+            ann.breakdown(Accounting::Member),
+            SizeBreakdown {
+                whitespace: 1 + 1 + 2 + 1 + 4 + 1 + 1 + 2,
+                comments: 7,
+                control: crate::size::ControlChars {
+                    quotes: 2,
+                    commas: 1,
+                    colons: 2,
+                    brackets: 0,
+                    other: 0,
+                },
+                data_size: "nameAnnage30".len(),
+            }
+        );
+
+        let path = file
+            .js
+            .json_pointer_path(&file.json, "/users/1/tags")
+            .unwrap();
+        let tags = file.js.get_path(&path).unwrap();
+        assert_eq!(tags.total_size(), " [a, 'b c']\n".len());
+        assert_eq!(tags.children()[0].member_size(), "a,".len());
+        assert_eq!(tags.children()[1].member_size(), " 'b c'".len());
+    }
+
+    #[test]
+    fn test_anchors_and_documents() {
+        let yaml = "base: &base\n  image: nginx\n  port: 80\na: *base\nb: *base\n---\n- !!str 12\n- 0x1F\n- .5\n- ~\n";
+        let file = parse_yaml(yaml).unwrap();
+        assert_eq!(
+            file.json,
+            r#"[{"base":{"image":"nginx","port":80},"a":"*base","b":"*base"},["12",31,0.5,null]]"#
+        );
+        assert_eq!(file.js.total_size(), yaml.len());
+        assert_eq!(
+            file.anchors,
+            vec![Anchor {
+                name: "base".to_string(),
+                pointer: "/0/base".to_string(),
+                size: " &base\n  image: nginx\n  port: 80\n".len(),
+                references: 2,
+            }]
+        );
+        let documents = file.js.children();
+        assert_eq!(documents[0].total_size(), 56);
+        // The `---` marker is part of the second document
+        assert_eq!(documents[1].member_size(), yaml.len() - 56);
+    }
+
+    #[test]
+    fn test_redefined_anchor() {
+        let file = parse_yaml("a: &x 1\nb: &x 2\nc: *x\n").unwrap();
+        let references: Vec<_> = file
+            .anchors
+            .iter()
+            .map(|x| (x.pointer.as_str(), x.references))
+            .collect();
+        assert_eq!(references, vec![("/a", 0), ("/b", 1)]);
+    }
+
+    #[test]
+    fn test_locate() {
+        let yaml = "users:\n  - name: Ann\n    tags: [a, b]\nn: 2\n";
+        let file = parse_yaml(yaml).unwrap();
+        let locate = |needle: &str| {
+            let location = file.locate(yaml.find(needle).unwrap());
+            let pointer = file
+                .js
+                .path_to_json_pointer(&file.json, &location.path)
+                .unwrap();
+            (pointer, location.in_key)
+        };
+        assert_eq!(locate("Ann"), ("/users/0/name".to_string(), false));
+        assert_eq!(locate("name"), ("/users/0/name".to_string(), true));
+        assert_eq!(locate("- "), ("/users".to_string(), false));
+        assert_eq!(locate("b]"), ("/users/0/tags/1".to_string(), false));
+        assert_eq!(locate(", b"), ("/users/0/tags".to_string(), false));
+        assert_eq!(locate("2"), ("/n".to_string(), false));
+        assert_eq!(locate("users"), ("/users".to_string(), true));
+    }
+
+    #[test]
+    fn test_encoding() {
+        let decoded = |bytes: &[u8]| crate::encoding::decode(bytes.to_vec()).unwrap();
+        let file = parse_yaml_decoded(&decoded(b"\xEF\xBB\xBFa: 1")).unwrap();
+        assert_eq!(file.js.total_size(), 7);
+
+        let utf16: Vec<u8> = "\u{FEFF}a: \u{e9}\n"
+            .encode_utf16()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        let file = parse_yaml_decoded(&decoded(&utf16)).unwrap();
+        assert_eq!(file.js.total_size(), utf16.len());
+        assert_eq!(
+            file.js.children()[0].total_size(),
+            " \u{e9}\n".len() * 2 - 2
+        );
+    }
+
+    #[test]
+    fn test_json_number() {
+        assert_eq!(json_number("+007").as_deref(), Some("7"));
+        assert_eq!(json_number("-0").as_deref(), Some("-0"));
+        assert_eq!(json_number("1.").as_deref(), Some("1.0"));
+        assert_eq!(json_number("-.5e+3").as_deref(), Some("-0.5e+3"));
+        assert_eq!(json_number("0o17").as_deref(), Some("15"));
+        assert_eq!(json_number(".inf"), None);
+        assert_eq!(json_number("1e"), None);
+        assert_eq!(json_number("1.2.3"), None);
+        assert_eq!(json_number("12:30"), None);
+    }
+}